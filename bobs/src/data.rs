@@ -1,5 +1,6 @@
 use crate::string::{cstring, string_ref};
 use crate::{ObsRawBox, ObsRawCounted};
+use std::marker::PhantomData;
 use std::ptr::NonNull;
 
 #[derive(Debug)]
@@ -170,7 +171,16 @@ impl Data {
         }
     }
 
-    // FIXME set_array
+    pub fn set_array(&mut self, name: &str, val: &DataArray) {
+        let cname = cstring(name);
+        unsafe {
+            obs_sys::obs_data_set_array(
+                self.as_raw().as_ptr(),
+                cname.as_ptr(),
+                val.as_raw().as_ptr(),
+            );
+        }
+    }
 
     pub fn get_string(&self, name: &str) -> &str {
         let cname = cstring(name);
@@ -207,7 +217,15 @@ impl Data {
         }
     }
 
-    // FIXME get_array
+    pub fn get_array(&self, name: &str) -> Option<Box<DataArray>> {
+        let cname = cstring(name);
+        unsafe {
+            Some(DataArray::from_raw(NonNull::new(obs_sys::obs_data_get_array(
+                self.as_raw().as_ptr(),
+                cname.as_ptr(),
+            ))?))
+        }
+    }
 
     pub fn set_default_string(&mut self, name: &str, val: &str) {
         let cname = cstring(name);
@@ -291,5 +309,343 @@ impl Data {
         }
     }
 
+    pub fn set_default_array(&mut self, name: &str, arr: &DataArray) {
+        let cname = cstring(name);
+        unsafe {
+            obs_sys::obs_data_set_default_array(
+                self.as_raw().as_ptr(),
+                cname.as_ptr(),
+                arr.as_raw().as_ptr(),
+            );
+        }
+    }
+
+    pub fn get_default_array(&self, name: &str) -> Option<Box<DataArray>> {
+        let cname = cstring(name);
+        unsafe {
+            Some(DataArray::from_raw(NonNull::new(
+                obs_sys::obs_data_get_default_array(self.as_raw().as_ptr(), cname.as_ptr()),
+            )?))
+        }
+    }
+
+    pub fn has_user_value(&self, name: &str) -> bool {
+        let cname = cstring(name);
+        unsafe { obs_sys::obs_data_has_user_value(self.as_raw().as_ptr(), cname.as_ptr()) }
+    }
+
+    pub fn has_default_value(&self, name: &str) -> bool {
+        let cname = cstring(name);
+        unsafe { obs_sys::obs_data_has_default_value(self.as_raw().as_ptr(), cname.as_ptr()) }
+    }
+
+    // go back to the default, if any
+    pub fn unset_user_value(&mut self, name: &str) {
+        let cname = cstring(name);
+        unsafe {
+            obs_sys::obs_data_unset_user_value(self.as_raw().as_ptr(), cname.as_ptr());
+        }
+    }
+
+    pub fn item(&self, name: &str) -> Option<DataItem<'_>> {
+        let cname = cstring(name);
+        let raw =
+            unsafe { obs_sys::obs_data_item_byname(self.as_raw().as_ptr(), cname.as_ptr()) };
+        Some(DataItem {
+            raw: NonNull::new(raw)?,
+            data: PhantomData,
+        })
+    }
+
+    // every item with a user or default value
+    pub fn items(&self) -> DataItems<'_> {
+        DataItems {
+            data: self,
+            cursor: unsafe { obs_sys::obs_data_first(self.as_raw().as_ptr()) },
+        }
+    }
+
     // FIXME autoselect
 }
+
+#[derive(Debug)]
+pub struct DataItem<'a> {
+    raw: NonNull<obs_sys::obs_data_item_t>,
+    data: PhantomData<&'a Data>,
+}
+
+impl<'a> Drop for DataItem<'a> {
+    fn drop(&mut self) {
+        let mut raw = self.raw.as_ptr();
+        unsafe {
+            obs_sys::obs_data_item_release(&mut raw);
+        }
+    }
+}
+
+impl<'a> DataItem<'a> {
+    pub fn name(&self) -> &str {
+        unsafe { string_ref(obs_sys::obs_data_item_get_name(self.raw.as_ptr())) }
+    }
+
+    pub fn type_(&self) -> Option<DataType> {
+        DataType::from_raw(unsafe { obs_sys::obs_data_item_gettype(self.raw.as_ptr()) })
+    }
+
+    // for numbers, whether it's stored as an int or a double
+    pub fn number_type(&self) -> Option<NumberType> {
+        NumberType::from_raw(unsafe { obs_sys::obs_data_item_numtype(self.raw.as_ptr()) })
+    }
+
+    pub fn has_user_value(&self) -> bool {
+        unsafe { obs_sys::obs_data_item_has_user_value(self.raw.as_ptr()) }
+    }
+
+    pub fn has_default_value(&self) -> bool {
+        unsafe { obs_sys::obs_data_item_has_default_value(self.raw.as_ptr()) }
+    }
+
+    pub fn get_string(&self) -> &str {
+        unsafe { string_ref(obs_sys::obs_data_item_get_string(self.raw.as_ptr())) }
+    }
+
+    pub fn get_int(&self) -> i64 {
+        unsafe { obs_sys::obs_data_item_get_int(self.raw.as_ptr()) as i64 }
+    }
+
+    pub fn get_double(&self) -> f64 {
+        unsafe { obs_sys::obs_data_item_get_double(self.raw.as_ptr()) }
+    }
+
+    pub fn get_bool(&self) -> bool {
+        unsafe { obs_sys::obs_data_item_get_bool(self.raw.as_ptr()) }
+    }
+
+    pub fn get_default_string(&self) -> &str {
+        unsafe { string_ref(obs_sys::obs_data_item_get_default_string(self.raw.as_ptr())) }
+    }
+
+    pub fn get_default_int(&self) -> i64 {
+        unsafe { obs_sys::obs_data_item_get_default_int(self.raw.as_ptr()) as i64 }
+    }
+
+    pub fn get_default_double(&self) -> f64 {
+        unsafe { obs_sys::obs_data_item_get_default_double(self.raw.as_ptr()) }
+    }
+
+    pub fn get_default_bool(&self) -> bool {
+        unsafe { obs_sys::obs_data_item_get_default_bool(self.raw.as_ptr()) }
+    }
+}
+
+#[derive(Debug)]
+pub struct DataItems<'a> {
+    data: &'a Data,
+    // obs_data_item_next releases the item it moves off of, so each item
+    // handed out is looked up again by name and holds its own reference
+    cursor: *mut obs_sys::obs_data_item_t,
+}
+
+impl<'a> Iterator for DataItems<'a> {
+    type Item = DataItem<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.cursor.is_null() {
+            return None;
+        }
+        let name = unsafe { string_ref(obs_sys::obs_data_item_get_name(self.cursor)) };
+        let item = self.data.item(name);
+        unsafe {
+            obs_sys::obs_data_item_next(&mut self.cursor);
+        }
+        item
+    }
+}
+
+impl<'a> Drop for DataItems<'a> {
+    fn drop(&mut self) {
+        if !self.cursor.is_null() {
+            unsafe {
+                obs_sys::obs_data_item_release(&mut self.cursor);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DataType {
+    Null,
+    String,
+    Number,
+    Boolean,
+    Object,
+    Array,
+}
+
+impl DataType {
+    pub fn into_raw(self) -> obs_sys::obs_data_type {
+        match self {
+            DataType::Null => obs_sys::obs_data_type_OBS_DATA_NULL,
+            DataType::String => obs_sys::obs_data_type_OBS_DATA_STRING,
+            DataType::Number => obs_sys::obs_data_type_OBS_DATA_NUMBER,
+            DataType::Boolean => obs_sys::obs_data_type_OBS_DATA_BOOLEAN,
+            DataType::Object => obs_sys::obs_data_type_OBS_DATA_OBJECT,
+            DataType::Array => obs_sys::obs_data_type_OBS_DATA_ARRAY,
+        }
+    }
+
+    pub fn from_raw(raw: obs_sys::obs_data_type) -> Option<Self> {
+        match raw {
+            obs_sys::obs_data_type_OBS_DATA_NULL => Some(DataType::Null),
+            obs_sys::obs_data_type_OBS_DATA_STRING => Some(DataType::String),
+            obs_sys::obs_data_type_OBS_DATA_NUMBER => Some(DataType::Number),
+            obs_sys::obs_data_type_OBS_DATA_BOOLEAN => Some(DataType::Boolean),
+            obs_sys::obs_data_type_OBS_DATA_OBJECT => Some(DataType::Object),
+            obs_sys::obs_data_type_OBS_DATA_ARRAY => Some(DataType::Array),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum NumberType {
+    Int,
+    Double,
+}
+
+impl NumberType {
+    pub fn into_raw(self) -> obs_sys::obs_data_number_type {
+        match self {
+            NumberType::Int => obs_sys::obs_data_number_type_OBS_DATA_NUM_INT,
+            NumberType::Double => obs_sys::obs_data_number_type_OBS_DATA_NUM_DOUBLE,
+        }
+    }
+
+    pub fn from_raw(raw: obs_sys::obs_data_number_type) -> Option<Self> {
+        match raw {
+            obs_sys::obs_data_number_type_OBS_DATA_NUM_INT => Some(NumberType::Int),
+            obs_sys::obs_data_number_type_OBS_DATA_NUM_DOUBLE => Some(NumberType::Double),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct DataArray(obs_sys::obs_data_array_t);
+
+impl ObsRawBox for DataArray {
+    type Raw = NonNull<obs_sys::obs_data_array_t>;
+
+    unsafe fn from_raw(raw: Self::Raw) -> Box<Self> {
+        Box::from_raw(std::mem::transmute(raw.as_ptr()))
+    }
+
+    unsafe fn as_raw(&self) -> Self::Raw {
+        (&self.0).into()
+    }
+}
+
+impl ObsRawCounted for DataArray {
+    unsafe fn addref(&self) {
+        obs_sys::obs_data_array_addref(self.as_raw().as_ptr());
+    }
+
+    unsafe fn release(&self) {
+        obs_sys::obs_data_array_release(self.as_raw().as_ptr());
+    }
+}
+
+impl Drop for DataArray {
+    fn drop(&mut self) {
+        unsafe { self.release() }
+    }
+}
+
+impl DataArray {
+    pub fn create() -> Box<Self> {
+        unsafe {
+            Self::from_raw(NonNull::new(obs_sys::obs_data_array_create()).expect("pointer is null"))
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        unsafe { obs_sys::obs_data_array_count(self.as_raw().as_ptr()) as usize }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, idx: usize) -> Option<Box<Data>> {
+        if idx >= self.len() {
+            return None;
+        }
+        unsafe {
+            Some(Data::from_raw(NonNull::new(obs_sys::obs_data_array_item(
+                self.as_raw().as_ptr(),
+                idx as obs_sys::size_t,
+            ))?))
+        }
+    }
+
+    // returns the index of the new item
+    pub fn push(&mut self, obj: &Data) -> usize {
+        unsafe {
+            obs_sys::obs_data_array_push_back(self.as_raw().as_ptr(), obj.as_raw().as_ptr())
+                as usize
+        }
+    }
+
+    pub fn insert(&mut self, idx: usize, obj: &Data) {
+        assert!(idx <= self.len(), "insertion index out of bounds");
+        unsafe {
+            obs_sys::obs_data_array_insert(
+                self.as_raw().as_ptr(),
+                idx as obs_sys::size_t,
+                obj.as_raw().as_ptr(),
+            );
+        }
+    }
+
+    pub fn erase(&mut self, idx: usize) {
+        assert!(idx < self.len(), "removal index out of bounds");
+        unsafe {
+            obs_sys::obs_data_array_erase(self.as_raw().as_ptr(), idx as obs_sys::size_t);
+        }
+    }
+
+    pub fn iter(&self) -> DataArrayIter<'_> {
+        DataArrayIter {
+            array: self,
+            idx: 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DataArrayIter<'a> {
+    array: &'a DataArray,
+    idx: usize,
+}
+
+impl<'a> Iterator for DataArrayIter<'a> {
+    type Item = Box<Data>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.array.get(self.idx)?;
+        self.idx += 1;
+        Some(item)
+    }
+}
+
+impl<'a> IntoIterator for &'a DataArray {
+    type Item = Box<Data>;
+    type IntoIter = DataArrayIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
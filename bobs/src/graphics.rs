@@ -0,0 +1,360 @@
+use crate::string::{cstring, string_ref};
+use crate::ObsRawBox;
+use std::ptr::NonNull;
+
+// holds the graphics context until dropped. video_render already runs
+// inside it, and entering again from there is fine.
+#[derive(Debug)]
+pub struct GraphicsContext {
+    _private: (),
+}
+
+impl GraphicsContext {
+    pub fn enter() -> Self {
+        unsafe { obs_sys::obs_enter_graphics() };
+        GraphicsContext { _private: () }
+    }
+}
+
+impl Drop for GraphicsContext {
+    fn drop(&mut self) {
+        unsafe { obs_sys::obs_leave_graphics() };
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ColorFormat {
+    A8,
+    R8,
+    Rgba,
+    Bgrx,
+    Bgra,
+}
+
+impl ColorFormat {
+    pub fn into_raw(self) -> obs_sys::gs_color_format {
+        match self {
+            ColorFormat::A8 => obs_sys::gs_color_format_GS_A8,
+            ColorFormat::R8 => obs_sys::gs_color_format_GS_R8,
+            ColorFormat::Rgba => obs_sys::gs_color_format_GS_RGBA,
+            ColorFormat::Bgrx => obs_sys::gs_color_format_GS_BGRX,
+            ColorFormat::Bgra => obs_sys::gs_color_format_GS_BGRA,
+        }
+    }
+
+    pub fn from_raw(raw: obs_sys::gs_color_format) -> Option<Self> {
+        match raw {
+            obs_sys::gs_color_format_GS_A8 => Some(ColorFormat::A8),
+            obs_sys::gs_color_format_GS_R8 => Some(ColorFormat::R8),
+            obs_sys::gs_color_format_GS_RGBA => Some(ColorFormat::Rgba),
+            obs_sys::gs_color_format_GS_BGRX => Some(ColorFormat::Bgrx),
+            obs_sys::gs_color_format_GS_BGRA => Some(ColorFormat::Bgra),
+            _ => None,
+        }
+    }
+
+    pub fn bytes_per_pixel(self) -> u32 {
+        match self {
+            ColorFormat::A8 | ColorFormat::R8 => 1,
+            ColorFormat::Rgba | ColorFormat::Bgrx | ColorFormat::Bgra => 4,
+        }
+    }
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct Texture(obs_sys::gs_texture_t);
+
+impl ObsRawBox for Texture {
+    type Raw = NonNull<obs_sys::gs_texture_t>;
+
+    unsafe fn from_raw(raw: Self::Raw) -> Box<Self> {
+        Box::from_raw(std::mem::transmute(raw.as_ptr()))
+    }
+
+    unsafe fn as_raw(&self) -> Self::Raw {
+        (&self.0).into()
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        let _ctx = GraphicsContext::enter();
+        unsafe { obs_sys::gs_texture_destroy(self.as_raw().as_ptr()) }
+    }
+}
+
+impl Texture {
+    // a texture that can be written with set_image, once per frame at most
+    pub fn create_dynamic(width: u32, height: u32, format: ColorFormat) -> Option<Box<Self>> {
+        let _ctx = GraphicsContext::enter();
+        unsafe {
+            Some(Self::from_raw(NonNull::new(obs_sys::gs_texture_create(
+                width,
+                height,
+                format.into_raw(),
+                1,
+                std::ptr::null_mut(),
+                obs_sys::GS_DYNAMIC,
+            ))?))
+        }
+    }
+
+    // a texture that never changes, from tightly packed rows
+    pub fn create_static(
+        width: u32,
+        height: u32,
+        format: ColorFormat,
+        data: &[u8],
+    ) -> Option<Box<Self>> {
+        let size = (width * height * format.bytes_per_pixel()) as usize;
+        assert!(data.len() >= size, "texture data too short");
+        let _ctx = GraphicsContext::enter();
+        let mut levels = [data.as_ptr()];
+        unsafe {
+            Some(Self::from_raw(NonNull::new(obs_sys::gs_texture_create(
+                width,
+                height,
+                format.into_raw(),
+                1,
+                levels.as_mut_ptr(),
+                0,
+            ))?))
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        unsafe { obs_sys::gs_texture_get_width(self.as_raw().as_ptr()) }
+    }
+
+    pub fn height(&self) -> u32 {
+        unsafe { obs_sys::gs_texture_get_height(self.as_raw().as_ptr()) }
+    }
+
+    pub fn format(&self) -> Option<ColorFormat> {
+        ColorFormat::from_raw(unsafe {
+            obs_sys::gs_texture_get_color_format(self.as_raw().as_ptr())
+        })
+    }
+
+    // replace the contents of a dynamic texture, rows `linesize` bytes apart
+    pub fn set_image(&mut self, data: &[u8], linesize: u32, flip: bool) {
+        let bpp = self.format().map(ColorFormat::bytes_per_pixel).unwrap_or(4);
+        assert!(linesize >= self.width() * bpp, "linesize too short");
+        assert!(
+            data.len() >= (linesize * self.height()) as usize,
+            "texture data too short"
+        );
+        let _ctx = GraphicsContext::enter();
+        unsafe {
+            obs_sys::gs_texture_set_image(self.as_raw().as_ptr(), data.as_ptr(), linesize, flip);
+        }
+    }
+
+    // draw with whatever effect is looping. 0 means the texture's own size.
+    pub fn draw(&self, width: u32, height: u32) {
+        unsafe { obs_sys::gs_draw_sprite(self.as_raw().as_ptr(), 0, width, height) }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ScaleFilter {
+    Point,
+    Linear,
+    Anisotropic,
+}
+
+impl ScaleFilter {
+    pub fn into_raw(self) -> obs_sys::gs_sample_filter {
+        match self {
+            ScaleFilter::Point => obs_sys::gs_sample_filter_GS_FILTER_POINT,
+            ScaleFilter::Linear => obs_sys::gs_sample_filter_GS_FILTER_LINEAR,
+            ScaleFilter::Anisotropic => obs_sys::gs_sample_filter_GS_FILTER_ANISOTROPIC,
+        }
+    }
+
+    pub fn from_raw(raw: obs_sys::gs_sample_filter) -> Option<Self> {
+        match raw {
+            obs_sys::gs_sample_filter_GS_FILTER_POINT => Some(ScaleFilter::Point),
+            obs_sys::gs_sample_filter_GS_FILTER_LINEAR => Some(ScaleFilter::Linear),
+            obs_sys::gs_sample_filter_GS_FILTER_ANISOTROPIC => Some(ScaleFilter::Anisotropic),
+            _ => None,
+        }
+    }
+}
+
+// how a texture is sampled, overriding the effect's own sampler
+#[derive(Debug)]
+#[repr(C)]
+pub struct Sampler(obs_sys::gs_samplerstate_t);
+
+impl ObsRawBox for Sampler {
+    type Raw = NonNull<obs_sys::gs_samplerstate_t>;
+
+    unsafe fn from_raw(raw: Self::Raw) -> Box<Self> {
+        Box::from_raw(std::mem::transmute(raw.as_ptr()))
+    }
+
+    unsafe fn as_raw(&self) -> Self::Raw {
+        (&self.0).into()
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        let _ctx = GraphicsContext::enter();
+        unsafe { obs_sys::gs_samplerstate_destroy(self.as_raw().as_ptr()) }
+    }
+}
+
+impl Sampler {
+    // clamped at the edges, with the given filter
+    pub fn create(filter: ScaleFilter) -> Option<Box<Self>> {
+        let info = obs_sys::gs_sampler_info {
+            filter: filter.into_raw(),
+            address_u: obs_sys::gs_address_mode_GS_ADDRESS_CLAMP,
+            address_v: obs_sys::gs_address_mode_GS_ADDRESS_CLAMP,
+            address_w: obs_sys::gs_address_mode_GS_ADDRESS_CLAMP,
+            max_anisotropy: 1,
+            border_color: 0,
+        };
+        let _ctx = GraphicsContext::enter();
+        unsafe { Some(Self::from_raw(NonNull::new(obs_sys::gs_samplerstate_create(&info))?)) }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum BaseEffect {
+    Default,
+    DefaultRect,
+    Opaque,
+    Solid,
+}
+
+impl BaseEffect {
+    pub fn into_raw(self) -> obs_sys::obs_base_effect {
+        match self {
+            BaseEffect::Default => obs_sys::obs_base_effect_OBS_EFFECT_DEFAULT,
+            BaseEffect::DefaultRect => obs_sys::obs_base_effect_OBS_EFFECT_DEFAULT_RECT,
+            BaseEffect::Opaque => obs_sys::obs_base_effect_OBS_EFFECT_OPAQUE,
+            BaseEffect::Solid => obs_sys::obs_base_effect_OBS_EFFECT_SOLID,
+        }
+    }
+}
+
+// a shader effect. the base effects belong to OBS, so those are only ever
+// borrowed, and only ones from create are boxed and destroyed.
+#[derive(Debug)]
+#[repr(C)]
+pub struct Effect(obs_sys::gs_effect_t);
+
+impl ObsRawBox for Effect {
+    type Raw = NonNull<obs_sys::gs_effect_t>;
+
+    unsafe fn from_raw(raw: Self::Raw) -> Box<Self> {
+        Box::from_raw(std::mem::transmute(raw.as_ptr()))
+    }
+
+    unsafe fn as_raw(&self) -> Self::Raw {
+        (&self.0).into()
+    }
+}
+
+impl Drop for Effect {
+    fn drop(&mut self) {
+        let _ctx = GraphicsContext::enter();
+        unsafe { obs_sys::gs_effect_destroy(self.as_raw().as_ptr()) }
+    }
+}
+
+impl Effect {
+    pub fn base(effect: BaseEffect) -> &'static Effect {
+        let raw = unsafe { obs_sys::obs_get_base_effect(effect.into_raw()) };
+        let raw = NonNull::new(raw).expect("pointer is null");
+        unsafe { &*(raw.as_ptr() as *const Effect) }
+    }
+
+    // compile the contents of an .effect file. name is only used in errors.
+    pub fn create(source: &str, name: &str) -> Option<Box<Self>> {
+        let csource = cstring(source);
+        let cname = cstring(name);
+        let mut error = std::ptr::null_mut();
+        let _ctx = GraphicsContext::enter();
+        unsafe {
+            let raw = obs_sys::gs_effect_create(csource.as_ptr(), cname.as_ptr(), &mut error);
+            if !error.is_null() {
+                log::warn!("could not compile {}: {}", name, string_ref(error));
+                obs_sys::bfree(error as *mut std::ffi::c_void);
+            }
+            Some(Self::from_raw(NonNull::new(raw)?))
+        }
+    }
+
+    pub fn param(&self, name: &str) -> Option<&EffectParam> {
+        let cname = cstring(name);
+        unsafe {
+            let raw = obs_sys::gs_effect_get_param_by_name(self.as_raw().as_ptr(), cname.as_ptr());
+            Some(&*(NonNull::new(raw)?.as_ptr() as *const EffectParam))
+        }
+    }
+
+    // call draw once per pass of the technique
+    pub fn draw<F>(&self, technique: &str, mut draw: F)
+    where
+        F: FnMut(),
+    {
+        let ctechnique = cstring(technique);
+        unsafe {
+            while obs_sys::gs_effect_loop(self.as_raw().as_ptr(), ctechnique.as_ptr()) {
+                draw();
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct EffectParam(obs_sys::gs_eparam_t);
+
+impl EffectParam {
+    unsafe fn as_raw(&self) -> NonNull<obs_sys::gs_eparam_t> {
+        (&self.0).into()
+    }
+
+    pub fn set_texture(&self, texture: &Texture) {
+        unsafe { obs_sys::gs_effect_set_texture(self.as_raw().as_ptr(), texture.as_raw().as_ptr()) }
+    }
+
+    // call before set_texture, and it applies to that texture only
+    pub fn set_next_sampler(&self, sampler: &Sampler) {
+        unsafe {
+            obs_sys::gs_effect_set_next_sampler(self.as_raw().as_ptr(), sampler.as_raw().as_ptr())
+        }
+    }
+
+    pub fn set_vec3(&self, value: [f32; 3]) {
+        self.set_val(&value);
+    }
+
+    pub fn set_vec4(&self, value: [f32; 4]) {
+        self.set_val(&value);
+    }
+
+    // rows, as OBS's matrix4 stores them
+    pub fn set_matrix4(&self, value: [[f32; 4]; 4]) {
+        self.set_val(&value);
+    }
+
+    fn set_val<T: Copy>(&self, value: &T) {
+        unsafe {
+            obs_sys::gs_effect_set_val(
+                self.as_raw().as_ptr(),
+                value as *const T as *const std::ffi::c_void,
+                std::mem::size_of::<T>() as obs_sys::size_t,
+            )
+        }
+    }
+}
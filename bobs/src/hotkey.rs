@@ -0,0 +1,281 @@
+use crate::string::cstring;
+use crate::{Data, DataArray, ObsRawBox, Source};
+use std::ffi::c_void;
+use std::ptr::NonNull;
+
+pub type HotkeyId = obs_sys::obs_hotkey_id;
+pub type HotkeyPairId = obs_sys::obs_hotkey_pair_id;
+
+// what OBS hands back when registration fails
+const INVALID_HOTKEY_ID: HotkeyId = !0;
+const INVALID_HOTKEY_PAIR_ID: HotkeyPairId = !0;
+
+// called with true on press, and false on release
+type Callback = Box<dyn FnMut(bool) + Send>;
+
+// like Callback, but returns whether it did anything
+type PairCallback = Box<dyn FnMut(bool) -> bool + Send>;
+
+// a hotkey with a closure behind it, unregistered when dropped. callbacks
+// run on the hotkey thread.
+//
+// source hotkeys keep their bindings with the source already. frontend
+// hotkeys don't, so use save_to and load_from in the owner's save and load.
+pub struct Hotkey {
+    id: HotkeyId,
+    // boxed again so the pointer OBS holds doesn't move
+    _callback: Box<Callback>,
+}
+
+impl std::fmt::Debug for Hotkey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Hotkey").field("id", &self.id).finish()
+    }
+}
+
+impl Hotkey {
+    // listed under the source in the hotkey settings
+    pub fn register_source<F>(
+        source: &Source,
+        name: &str,
+        description: &str,
+        callback: F,
+    ) -> Option<Self>
+    where
+        F: FnMut(bool) + Send + 'static,
+    {
+        let cname = cstring(name);
+        let cdescription = cstring(description);
+        let mut callback: Box<Callback> = Box::new(Box::new(callback));
+        let id = unsafe {
+            obs_sys::obs_hotkey_register_source(
+                source.as_raw().as_ptr(),
+                cname.as_ptr(),
+                cdescription.as_ptr(),
+                Some(pressed),
+                &mut *callback as *mut Callback as *mut c_void,
+            )
+        };
+        Self::registered(id, callback)
+    }
+
+    // listed with OBS's own hotkeys
+    pub fn register_frontend<F>(name: &str, description: &str, callback: F) -> Option<Self>
+    where
+        F: FnMut(bool) + Send + 'static,
+    {
+        let cname = cstring(name);
+        let cdescription = cstring(description);
+        let mut callback: Box<Callback> = Box::new(Box::new(callback));
+        let id = unsafe {
+            obs_sys::obs_hotkey_register_frontend(
+                cname.as_ptr(),
+                cdescription.as_ptr(),
+                Some(pressed),
+                &mut *callback as *mut Callback as *mut c_void,
+            )
+        };
+        Self::registered(id, callback)
+    }
+
+    fn registered(id: HotkeyId, callback: Box<Callback>) -> Option<Self> {
+        if id == INVALID_HOTKEY_ID {
+            return None;
+        }
+        Some(Hotkey {
+            id,
+            _callback: callback,
+        })
+    }
+
+    pub fn id(&self) -> HotkeyId {
+        self.id
+    }
+
+    // the keys bound to this hotkey
+    pub fn save(&self) -> Box<DataArray> {
+        unsafe {
+            DataArray::from_raw(
+                NonNull::new(obs_sys::obs_hotkey_save(self.id)).expect("pointer is null"),
+            )
+        }
+    }
+
+    pub fn load(&self, bindings: &DataArray) {
+        unsafe { obs_sys::obs_hotkey_load(self.id, bindings.as_raw().as_ptr()) }
+    }
+
+    pub fn save_to(&self, data: &mut Data, name: &str) {
+        data.set_array(name, &self.save());
+    }
+
+    // leaves the bindings alone if nothing was saved
+    pub fn load_from(&self, data: &Data, name: &str) {
+        if let Some(bindings) = data.get_array(name) {
+            self.load(&bindings);
+        }
+    }
+}
+
+impl Drop for Hotkey {
+    fn drop(&mut self) {
+        // once this returns, the callback won't be called again
+        unsafe { obs_sys::obs_hotkey_unregister(self.id) }
+    }
+}
+
+// two hotkeys for the two halves of a toggle, like start and stop. each
+// callback returns whether it did anything, so that binding both to one
+// key flips between them.
+pub struct HotkeyPair {
+    id: HotkeyPairId,
+    _callbacks: (Box<PairCallback>, Box<PairCallback>),
+}
+
+impl std::fmt::Debug for HotkeyPair {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("HotkeyPair").field("id", &self.id).finish()
+    }
+}
+
+impl HotkeyPair {
+    // names and descriptions are (first, second)
+    pub fn register_source<F0, F1>(
+        source: &Source,
+        names: (&str, &str),
+        descriptions: (&str, &str),
+        first: F0,
+        second: F1,
+    ) -> Option<Self>
+    where
+        F0: FnMut(bool) -> bool + Send + 'static,
+        F1: FnMut(bool) -> bool + Send + 'static,
+    {
+        let cnames = (cstring(names.0), cstring(names.1));
+        let cdescriptions = (cstring(descriptions.0), cstring(descriptions.1));
+        let mut callbacks: (Box<PairCallback>, Box<PairCallback>) =
+            (Box::new(Box::new(first)), Box::new(Box::new(second)));
+        let id = unsafe {
+            obs_sys::obs_hotkey_pair_register_source(
+                source.as_raw().as_ptr(),
+                cnames.0.as_ptr(),
+                cdescriptions.0.as_ptr(),
+                cnames.1.as_ptr(),
+                cdescriptions.1.as_ptr(),
+                Some(pair_pressed),
+                Some(pair_pressed),
+                &mut *callbacks.0 as *mut PairCallback as *mut c_void,
+                &mut *callbacks.1 as *mut PairCallback as *mut c_void,
+            )
+        };
+        Self::registered(id, callbacks)
+    }
+
+    pub fn register_frontend<F0, F1>(
+        names: (&str, &str),
+        descriptions: (&str, &str),
+        first: F0,
+        second: F1,
+    ) -> Option<Self>
+    where
+        F0: FnMut(bool) -> bool + Send + 'static,
+        F1: FnMut(bool) -> bool + Send + 'static,
+    {
+        let cnames = (cstring(names.0), cstring(names.1));
+        let cdescriptions = (cstring(descriptions.0), cstring(descriptions.1));
+        let mut callbacks: (Box<PairCallback>, Box<PairCallback>) =
+            (Box::new(Box::new(first)), Box::new(Box::new(second)));
+        let id = unsafe {
+            obs_sys::obs_hotkey_pair_register_frontend(
+                cnames.0.as_ptr(),
+                cdescriptions.0.as_ptr(),
+                cnames.1.as_ptr(),
+                cdescriptions.1.as_ptr(),
+                Some(pair_pressed),
+                Some(pair_pressed),
+                &mut *callbacks.0 as *mut PairCallback as *mut c_void,
+                &mut *callbacks.1 as *mut PairCallback as *mut c_void,
+            )
+        };
+        Self::registered(id, callbacks)
+    }
+
+    fn registered(
+        id: HotkeyPairId,
+        callbacks: (Box<PairCallback>, Box<PairCallback>),
+    ) -> Option<Self> {
+        if id == INVALID_HOTKEY_PAIR_ID {
+            return None;
+        }
+        Some(HotkeyPair {
+            id,
+            _callbacks: callbacks,
+        })
+    }
+
+    pub fn id(&self) -> HotkeyPairId {
+        self.id
+    }
+
+    // the keys bound to each half
+    pub fn save(&self) -> (Box<DataArray>, Box<DataArray>) {
+        let mut first = std::ptr::null_mut();
+        let mut second = std::ptr::null_mut();
+        unsafe {
+            obs_sys::obs_hotkey_pair_save(self.id, &mut first, &mut second);
+            (
+                DataArray::from_raw(NonNull::new(first).expect("pointer is null")),
+                DataArray::from_raw(NonNull::new(second).expect("pointer is null")),
+            )
+        }
+    }
+
+    pub fn load(&self, first: &DataArray, second: &DataArray) {
+        unsafe {
+            obs_sys::obs_hotkey_pair_load(
+                self.id,
+                first.as_raw().as_ptr(),
+                second.as_raw().as_ptr(),
+            )
+        }
+    }
+
+    // stored under names.0 and names.1
+    pub fn save_to(&self, data: &mut Data, names: (&str, &str)) {
+        let (first, second) = self.save();
+        data.set_array(names.0, &first);
+        data.set_array(names.1, &second);
+    }
+
+    pub fn load_from(&self, data: &Data, names: (&str, &str)) {
+        if let (Some(first), Some(second)) = (data.get_array(names.0), data.get_array(names.1)) {
+            self.load(&first, &second);
+        }
+    }
+}
+
+impl Drop for HotkeyPair {
+    fn drop(&mut self) {
+        unsafe { obs_sys::obs_hotkey_pair_unregister(self.id) }
+    }
+}
+
+unsafe extern "C" fn pressed(
+    data: *mut c_void,
+    _id: HotkeyId,
+    _hotkey: *mut obs_sys::obs_hotkey_t,
+    pressed: bool,
+) {
+    let callback = &mut *(data as *mut Callback);
+    callback(pressed);
+}
+
+unsafe extern "C" fn pair_pressed(
+    data: *mut c_void,
+    _id: HotkeyPairId,
+    _hotkey: *mut obs_sys::obs_hotkey_t,
+    pressed: bool,
+) -> bool {
+    let callback = &mut *(data as *mut PairCallback);
+    callback(pressed)
+}
@@ -1,21 +1,29 @@
 pub mod alloc;
 mod data;
+mod graphics;
+mod hotkey;
 pub mod log;
 mod module;
 mod properties;
 mod raw;
 mod register;
+mod settings;
 mod source;
 mod source_info;
 pub(crate) mod string;
+mod video;
 
 pub use data::*;
+pub use graphics::*;
+pub use hotkey::*;
 pub use module::*;
 pub use properties::*;
 pub use raw::*;
 pub use register::*;
+pub use settings::*;
 pub use source::*;
 pub use source_info::*;
+pub use video::*;
 
 pub mod prelude {
     pub use crate::{ObsRawBox, ObsRawCounted, ObsRawWeak};
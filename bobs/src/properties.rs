@@ -1,7 +1,68 @@
-use crate::string::cstring;
-use crate::ObsRawBox;
+use crate::string::{cstring, string_ref};
+use crate::{Data, ObsRawBox, ObsRawCounted};
+use std::collections::HashMap;
+use std::os::raw::c_void;
 use std::ptr::NonNull;
 
+type Clicked = Box<dyn FnMut(&mut Properties, &mut Property) -> bool>;
+type Modified = Box<dyn FnMut(&mut Properties, &Data) -> bool>;
+
+// rust closures attached to a properties object, by property name. this is
+// stored as the properties' param, so obs frees it along with them.
+#[derive(Default)]
+struct Callbacks {
+    clicked: HashMap<String, Clicked>,
+    modified: HashMap<String, Modified>,
+}
+
+unsafe extern "C" fn destroy_callbacks(param: *mut c_void) {
+    drop(Box::from_raw(param as *mut Callbacks));
+}
+
+unsafe extern "C" fn clicked(
+    props: *mut obs_sys::obs_properties_t,
+    property: *mut obs_sys::obs_property_t,
+    _data: *mut c_void,
+) -> bool {
+    let mut props = Properties::from_raw(NonNull::new(props).expect("null pointer"));
+    let mut property = Property::from_raw(NonNull::new(property).expect("null pointer"));
+    let name = string_ref(obs_sys::obs_property_name(property.as_raw().as_ptr()));
+    // take the closure out while it runs, so it can add more buttons
+    let callback = props.callbacks().clicked.remove(name);
+    let refresh = match callback {
+        Some(mut f) => {
+            let refresh = f(&mut props, &mut property);
+            props.callbacks().clicked.entry(name.to_owned()).or_insert(f);
+            refresh
+        }
+        None => false,
+    };
+    std::mem::forget(property);
+    std::mem::forget(props);
+    refresh
+}
+
+unsafe extern "C" fn modified(
+    props: *mut obs_sys::obs_properties_t,
+    property: *mut obs_sys::obs_property_t,
+    settings: *mut obs_sys::obs_data_t,
+) -> bool {
+    let mut props = Properties::from_raw(NonNull::new(props).expect("null pointer"));
+    let settings = Data::from_raw_unowned(NonNull::new(settings).expect("null pointer"));
+    let name = string_ref(obs_sys::obs_property_name(property));
+    let callback = props.callbacks().modified.remove(name);
+    let refresh = match callback {
+        Some(mut f) => {
+            let refresh = f(&mut props, &settings);
+            props.callbacks().modified.entry(name.to_owned()).or_insert(f);
+            refresh
+        }
+        None => false,
+    };
+    std::mem::forget(props);
+    refresh
+}
+
 #[derive(Debug)]
 #[repr(C)]
 pub struct Properties(obs_sys::obs_properties_t);
@@ -33,6 +94,62 @@ impl Properties {
         }
     }
 
+    // obs calls back with the topmost properties, even for a property in a
+    // group, so that's where the closures are kept
+    fn callbacks(&mut self) -> &mut Callbacks {
+        unsafe {
+            let mut raw = self.as_raw().as_ptr();
+            loop {
+                let parent = obs_sys::obs_properties_get_parent(raw);
+                if parent.is_null() {
+                    break;
+                }
+                raw = parent;
+            }
+            let mut param = obs_sys::obs_properties_get_param(raw) as *mut Callbacks;
+            if param.is_null() {
+                param = Box::into_raw(Box::<Callbacks>::default());
+                obs_sys::obs_properties_set_param(
+                    raw,
+                    param as *mut c_void,
+                    Some(destroy_callbacks),
+                );
+            }
+            &mut *param
+        }
+    }
+
+    pub fn get(&mut self, name: &str) -> Option<Box<Property>> {
+        let cname = cstring(name);
+        unsafe {
+            NonNull::new(obs_sys::obs_properties_get(
+                self.as_raw().as_ptr(),
+                cname.as_ptr(),
+            ))
+            .map(|p| Property::from_raw(p))
+        }
+    }
+
+    // called when the named property changes, and once when the properties
+    // are first shown. return true if the properties need to be refreshed.
+    pub fn set_modified_callback<F>(&mut self, name: &str, callback: F)
+    where
+        F: FnMut(&mut Properties, &Data) -> bool + 'static,
+    {
+        let cname = cstring(name);
+        let property =
+            unsafe { obs_sys::obs_properties_get(self.as_raw().as_ptr(), cname.as_ptr()) };
+        if property.is_null() {
+            return;
+        }
+        self.callbacks()
+            .modified
+            .insert(name.to_owned(), Box::new(callback));
+        unsafe {
+            obs_sys::obs_property_set_modified_callback(property, Some(modified));
+        }
+    }
+
     pub fn add_bool(&mut self, name: &str, description: &str) -> Box<Property> {
         let cname = cstring(name);
         let cdesc = cstring(description);
@@ -63,6 +180,253 @@ impl Properties {
             )
         }
     }
+
+    pub fn add_int(
+        &mut self,
+        name: &str,
+        description: &str,
+        min: i32,
+        max: i32,
+        step: i32,
+    ) -> Box<Property> {
+        let cname = cstring(name);
+        let cdesc = cstring(description);
+        unsafe {
+            Property::from_raw(
+                NonNull::new(obs_sys::obs_properties_add_int(
+                    self.as_raw().as_ptr(),
+                    cname.as_ptr(),
+                    cdesc.as_ptr(),
+                    min,
+                    max,
+                    step,
+                ))
+                .expect("pointer is null"),
+            )
+        }
+    }
+
+    pub fn add_int_slider(
+        &mut self,
+        name: &str,
+        description: &str,
+        min: i32,
+        max: i32,
+        step: i32,
+    ) -> Box<Property> {
+        let cname = cstring(name);
+        let cdesc = cstring(description);
+        unsafe {
+            Property::from_raw(
+                NonNull::new(obs_sys::obs_properties_add_int_slider(
+                    self.as_raw().as_ptr(),
+                    cname.as_ptr(),
+                    cdesc.as_ptr(),
+                    min,
+                    max,
+                    step,
+                ))
+                .expect("pointer is null"),
+            )
+        }
+    }
+
+    pub fn add_color(&mut self, name: &str, description: &str) -> Box<Property> {
+        let cname = cstring(name);
+        let cdesc = cstring(description);
+        unsafe {
+            Property::from_raw(
+                NonNull::new(obs_sys::obs_properties_add_color(
+                    self.as_raw().as_ptr(),
+                    cname.as_ptr(),
+                    cdesc.as_ptr(),
+                ))
+                .expect("pointer is null"),
+            )
+        }
+    }
+
+    pub fn add_list(
+        &mut self,
+        name: &str,
+        description: &str,
+        type_: ComboType,
+        format: ComboFormat,
+    ) -> Box<Property> {
+        let cname = cstring(name);
+        let cdesc = cstring(description);
+        unsafe {
+            Property::from_raw(
+                NonNull::new(obs_sys::obs_properties_add_list(
+                    self.as_raw().as_ptr(),
+                    cname.as_ptr(),
+                    cdesc.as_ptr(),
+                    type_.into_raw(),
+                    format.into_raw(),
+                ))
+                .expect("pointer is null"),
+            )
+        }
+    }
+
+    pub fn add_float(
+        &mut self,
+        name: &str,
+        description: &str,
+        min: f64,
+        max: f64,
+        step: f64,
+    ) -> Box<Property> {
+        let cname = cstring(name);
+        let cdesc = cstring(description);
+        unsafe {
+            Property::from_raw(
+                NonNull::new(obs_sys::obs_properties_add_float(
+                    self.as_raw().as_ptr(),
+                    cname.as_ptr(),
+                    cdesc.as_ptr(),
+                    min,
+                    max,
+                    step,
+                ))
+                .expect("pointer is null"),
+            )
+        }
+    }
+
+    pub fn add_float_slider(
+        &mut self,
+        name: &str,
+        description: &str,
+        min: f64,
+        max: f64,
+        step: f64,
+    ) -> Box<Property> {
+        let cname = cstring(name);
+        let cdesc = cstring(description);
+        unsafe {
+            Property::from_raw(
+                NonNull::new(obs_sys::obs_properties_add_float_slider(
+                    self.as_raw().as_ptr(),
+                    cname.as_ptr(),
+                    cdesc.as_ptr(),
+                    min,
+                    max,
+                    step,
+                ))
+                .expect("pointer is null"),
+            )
+        }
+    }
+
+    // the closure returns true if the properties need to be refreshed
+    pub fn add_button<F>(&mut self, name: &str, text: &str, callback: F) -> Box<Property>
+    where
+        F: FnMut(&mut Properties, &mut Property) -> bool + 'static,
+    {
+        self.callbacks()
+            .clicked
+            .insert(name.to_owned(), Box::new(callback));
+        let cname = cstring(name);
+        let ctext = cstring(text);
+        unsafe {
+            Property::from_raw(
+                NonNull::new(obs_sys::obs_properties_add_button(
+                    self.as_raw().as_ptr(),
+                    cname.as_ptr(),
+                    ctext.as_ptr(),
+                    Some(clicked),
+                ))
+                .expect("pointer is null"),
+            )
+        }
+    }
+
+    // filter is in Qt's format, like "Images (*.png *.bmp);;All files (*.*)"
+    pub fn add_path(
+        &mut self,
+        name: &str,
+        description: &str,
+        type_: PathType,
+        filter: &str,
+        default_path: &str,
+    ) -> Box<Property> {
+        let cname = cstring(name);
+        let cdesc = cstring(description);
+        let cfilter = cstring(filter);
+        let cpath = cstring(default_path);
+        unsafe {
+            Property::from_raw(
+                NonNull::new(obs_sys::obs_properties_add_path(
+                    self.as_raw().as_ptr(),
+                    cname.as_ptr(),
+                    cdesc.as_ptr(),
+                    type_.into_raw(),
+                    cfilter.as_ptr(),
+                    cpath.as_ptr(),
+                ))
+                .expect("pointer is null"),
+            )
+        }
+    }
+
+    pub fn add_editable_list(
+        &mut self,
+        name: &str,
+        description: &str,
+        type_: EditableListType,
+        filter: &str,
+        default_path: &str,
+    ) -> Box<Property> {
+        let cname = cstring(name);
+        let cdesc = cstring(description);
+        let cfilter = cstring(filter);
+        let cpath = cstring(default_path);
+        unsafe {
+            Property::from_raw(
+                NonNull::new(obs_sys::obs_properties_add_editable_list(
+                    self.as_raw().as_ptr(),
+                    cname.as_ptr(),
+                    cdesc.as_ptr(),
+                    type_.into_raw(),
+                    cfilter.as_ptr(),
+                    cpath.as_ptr(),
+                ))
+                .expect("pointer is null"),
+            )
+        }
+    }
+
+    // the group takes ownership of its properties
+    pub fn add_group(
+        &mut self,
+        name: &str,
+        description: &str,
+        type_: GroupType,
+        mut group: Box<Properties>,
+    ) -> Box<Property> {
+        let cname = cstring(name);
+        let cdesc = cstring(description);
+        // closures added to the group so far move up to where obs will look
+        let moved = std::mem::take(group.callbacks());
+        let callbacks = self.callbacks();
+        callbacks.clicked.extend(moved.clicked);
+        callbacks.modified.extend(moved.modified);
+        unsafe {
+            let raw = group.as_raw().as_ptr();
+            std::mem::forget(group);
+            Property::from_raw(
+                NonNull::new(obs_sys::obs_properties_add_group(
+                    self.as_raw().as_ptr(),
+                    cname.as_ptr(),
+                    cdesc.as_ptr(),
+                    type_.into_raw(),
+                    raw,
+                ))
+                .expect("pointer is null"),
+            )
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -81,6 +445,84 @@ impl ObsRawBox for Property {
     }
 }
 
+impl Property {
+    pub fn name(&self) -> &str {
+        unsafe { string_ref(obs_sys::obs_property_name(self.as_raw().as_ptr())) }
+    }
+
+    pub fn visible(&self) -> bool {
+        unsafe { obs_sys::obs_property_visible(self.as_raw().as_ptr()) }
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        unsafe {
+            obs_sys::obs_property_set_visible(self.as_raw().as_ptr(), visible);
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        unsafe { obs_sys::obs_property_enabled(self.as_raw().as_ptr()) }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        unsafe {
+            obs_sys::obs_property_set_enabled(self.as_raw().as_ptr(), enabled);
+        }
+    }
+
+    pub fn set_description(&mut self, description: &str) {
+        let cdesc = cstring(description);
+        unsafe {
+            obs_sys::obs_property_set_description(self.as_raw().as_ptr(), cdesc.as_ptr());
+        }
+    }
+
+    // shown as a tooltip
+    pub fn set_long_description(&mut self, long_description: &str) {
+        let cdesc = cstring(long_description);
+        unsafe {
+            obs_sys::obs_property_set_long_description(self.as_raw().as_ptr(), cdesc.as_ptr());
+        }
+    }
+
+    pub fn list_add_string(&mut self, name: &str, val: &str) -> usize {
+        let cname = cstring(name);
+        let cval = cstring(val);
+        unsafe {
+            obs_sys::obs_property_list_add_string(
+                self.as_raw().as_ptr(),
+                cname.as_ptr(),
+                cval.as_ptr(),
+            ) as usize
+        }
+    }
+
+    pub fn list_add_int(&mut self, name: &str, val: i64) -> usize {
+        let cname = cstring(name);
+        unsafe {
+            obs_sys::obs_property_list_add_int(
+                self.as_raw().as_ptr(),
+                cname.as_ptr(),
+                val as std::os::raw::c_longlong,
+            ) as usize
+        }
+    }
+
+    pub fn list_add_float(&mut self, name: &str, val: f64) -> usize {
+        let cname = cstring(name);
+        unsafe {
+            obs_sys::obs_property_list_add_float(self.as_raw().as_ptr(), cname.as_ptr(), val)
+                as usize
+        }
+    }
+
+    pub fn list_clear(&mut self) {
+        unsafe {
+            obs_sys::obs_property_list_clear(self.as_raw().as_ptr());
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[non_exhaustive]
 pub enum TextType {
@@ -107,3 +549,216 @@ impl TextType {
         }
     }
 }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ComboType {
+    Editable,
+    List,
+}
+
+impl ComboType {
+    pub fn into_raw(self) -> obs_sys::obs_combo_type {
+        match self {
+            ComboType::Editable => obs_sys::obs_combo_type_OBS_COMBO_TYPE_EDITABLE,
+            ComboType::List => obs_sys::obs_combo_type_OBS_COMBO_TYPE_LIST,
+        }
+    }
+
+    pub fn from_raw(raw: obs_sys::obs_combo_type) -> Option<Self> {
+        match raw {
+            obs_sys::obs_combo_type_OBS_COMBO_TYPE_EDITABLE => Some(ComboType::Editable),
+            obs_sys::obs_combo_type_OBS_COMBO_TYPE_LIST => Some(ComboType::List),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ComboFormat {
+    Int,
+    Float,
+    String,
+}
+
+impl ComboFormat {
+    pub fn into_raw(self) -> obs_sys::obs_combo_format {
+        match self {
+            ComboFormat::Int => obs_sys::obs_combo_format_OBS_COMBO_FORMAT_INT,
+            ComboFormat::Float => obs_sys::obs_combo_format_OBS_COMBO_FORMAT_FLOAT,
+            ComboFormat::String => obs_sys::obs_combo_format_OBS_COMBO_FORMAT_STRING,
+        }
+    }
+
+    pub fn from_raw(raw: obs_sys::obs_combo_format) -> Option<Self> {
+        match raw {
+            obs_sys::obs_combo_format_OBS_COMBO_FORMAT_INT => Some(ComboFormat::Int),
+            obs_sys::obs_combo_format_OBS_COMBO_FORMAT_FLOAT => Some(ComboFormat::Float),
+            obs_sys::obs_combo_format_OBS_COMBO_FORMAT_STRING => Some(ComboFormat::String),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PathType {
+    File,
+    FileSave,
+    Directory,
+}
+
+impl PathType {
+    pub fn into_raw(self) -> obs_sys::obs_path_type {
+        match self {
+            PathType::File => obs_sys::obs_path_type_OBS_PATH_FILE,
+            PathType::FileSave => obs_sys::obs_path_type_OBS_PATH_FILE_SAVE,
+            PathType::Directory => obs_sys::obs_path_type_OBS_PATH_DIRECTORY,
+        }
+    }
+
+    pub fn from_raw(raw: obs_sys::obs_path_type) -> Option<Self> {
+        match raw {
+            obs_sys::obs_path_type_OBS_PATH_FILE => Some(PathType::File),
+            obs_sys::obs_path_type_OBS_PATH_FILE_SAVE => Some(PathType::FileSave),
+            obs_sys::obs_path_type_OBS_PATH_DIRECTORY => Some(PathType::Directory),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum EditableListType {
+    Strings,
+    Files,
+    FilesAndUrls,
+}
+
+impl EditableListType {
+    pub fn into_raw(self) -> obs_sys::obs_editable_list_type {
+        match self {
+            EditableListType::Strings => {
+                obs_sys::obs_editable_list_type_OBS_EDITABLE_LIST_TYPE_STRINGS
+            }
+            EditableListType::Files => obs_sys::obs_editable_list_type_OBS_EDITABLE_LIST_TYPE_FILES,
+            EditableListType::FilesAndUrls => {
+                obs_sys::obs_editable_list_type_OBS_EDITABLE_LIST_TYPE_FILES_AND_URLS
+            }
+        }
+    }
+
+    pub fn from_raw(raw: obs_sys::obs_editable_list_type) -> Option<Self> {
+        match raw {
+            obs_sys::obs_editable_list_type_OBS_EDITABLE_LIST_TYPE_STRINGS => {
+                Some(EditableListType::Strings)
+            }
+            obs_sys::obs_editable_list_type_OBS_EDITABLE_LIST_TYPE_FILES => {
+                Some(EditableListType::Files)
+            }
+            obs_sys::obs_editable_list_type_OBS_EDITABLE_LIST_TYPE_FILES_AND_URLS => {
+                Some(EditableListType::FilesAndUrls)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum GroupType {
+    Normal,
+    Checkable,
+}
+
+impl GroupType {
+    pub fn into_raw(self) -> obs_sys::obs_group_type {
+        match self {
+            GroupType::Normal => obs_sys::obs_group_type_OBS_GROUP_NORMAL,
+            GroupType::Checkable => obs_sys::obs_group_type_OBS_GROUP_CHECKABLE,
+        }
+    }
+
+    pub fn from_raw(raw: obs_sys::obs_group_type) -> Option<Self> {
+        match raw {
+            obs_sys::obs_group_type_OBS_GROUP_NORMAL => Some(GroupType::Normal),
+            obs_sys::obs_group_type_OBS_GROUP_CHECKABLE => Some(GroupType::Checkable),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // what obs does when a button is pressed or a property changes: call
+    // back with the topmost properties, wherever the property is
+    fn click(props: &mut Properties, name: &str) -> bool {
+        let button = props.get(name).expect("no such button");
+        unsafe {
+            obs_sys::obs_property_button_clicked(button.as_raw().as_ptr(), std::ptr::null_mut())
+        }
+    }
+
+    fn modify(props: &mut Properties, name: &str) -> bool {
+        let property = props.get(name).expect("no such property");
+        let settings = Data::create();
+        unsafe {
+            obs_sys::obs_property_modified(property.as_raw().as_ptr(), settings.as_raw().as_ptr())
+        }
+    }
+
+    // a properties tree with a group, which logs every callback by name
+    fn grouped(log: &Rc<RefCell<Vec<&'static str>>>) -> Box<Properties> {
+        let mut props = Properties::create();
+        let mut group = Properties::create();
+        group.add_bool("inner_bool", "Inner");
+        let l = log.clone();
+        group.add_button("inner_button", "Inner", move |_, _| {
+            l.borrow_mut().push("inner_button");
+            true
+        });
+        let l = log.clone();
+        group.set_modified_callback("inner_bool", move |_, _| {
+            l.borrow_mut().push("inner_bool");
+            false
+        });
+        props.add_group("group", "Group", GroupType::Normal, group);
+
+        let l = log.clone();
+        props.add_button("outer_button", "Outer", move |_, _| {
+            l.borrow_mut().push("outer_button");
+            true
+        });
+        props
+    }
+
+    #[test]
+    fn callbacks_in_groups_are_found() {
+        let log = Rc::new(RefCell::new(vec![]));
+        let mut props = grouped(&log);
+
+        assert!(click(&mut props, "inner_button"));
+        assert!(!modify(&mut props, "inner_bool"));
+        assert!(click(&mut props, "outer_button"));
+        assert_eq!(*log.borrow(), ["inner_button", "inner_bool", "outer_button"]);
+    }
+
+    #[test]
+    fn callbacks_set_from_the_top_reach_into_groups() {
+        let log = Rc::new(RefCell::new(vec![]));
+        let mut props = grouped(&log);
+        let l = log.clone();
+        props.set_modified_callback("inner_bool", move |_, _| {
+            l.borrow_mut().push("replaced");
+            true
+        });
+
+        assert!(modify(&mut props, "inner_bool"));
+        assert_eq!(*log.borrow(), ["replaced"]);
+    }
+}
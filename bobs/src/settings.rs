@@ -0,0 +1,248 @@
+use crate::{ComboFormat, ComboType, Data, Properties, Property, TextType};
+
+// a struct that maps onto source settings, usually made with settings!
+pub trait Settings: Sized {
+    fn defaults(data: &mut Data);
+    fn properties(props: &mut Properties);
+    fn from_data(data: &Data) -> Self;
+    fn to_data(&self, data: &mut Data);
+}
+
+// how a setting shows up in the properties view
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Widget {
+    Bool,
+    Text(TextType),
+    Int { min: i32, max: i32, step: i32 },
+    IntSlider { min: i32, max: i32, step: i32 },
+    Float { min: f64, max: f64, step: f64 },
+    FloatSlider { min: f64, max: f64, step: f64 },
+    Color,
+    // (description, value) pairs. leave it empty to fill it in by hand.
+    List(&'static [(&'static str, &'static str)]),
+}
+
+impl Widget {
+    pub fn add(&self, props: &mut Properties, name: &str, description: &str) -> Box<Property> {
+        match *self {
+            Widget::Bool => props.add_bool(name, description),
+            Widget::Text(type_) => props.add_text(name, description, type_),
+            Widget::Int { min, max, step } => props.add_int(name, description, min, max, step),
+            Widget::IntSlider { min, max, step } => {
+                props.add_int_slider(name, description, min, max, step)
+            }
+            Widget::Float { min, max, step } => props.add_float(name, description, min, max, step),
+            Widget::FloatSlider { min, max, step } => {
+                props.add_float_slider(name, description, min, max, step)
+            }
+            Widget::Color => props.add_color(name, description),
+            Widget::List(items) => {
+                let mut p = props.add_list(name, description, ComboType::List, ComboFormat::String);
+                for (desc, val) in items {
+                    p.list_add_string(desc, val);
+                }
+                p
+            }
+        }
+    }
+}
+
+// a type that can be stored in settings
+pub trait Field: Sized {
+    fn set_default(data: &mut Data, name: &str, value: &Self);
+    fn get(data: &Data, name: &str) -> Self;
+    fn set(data: &mut Data, name: &str, value: &Self);
+
+    // bring a value back within what the widget allows, or None to use
+    // the default instead
+    fn validate(self, _widget: &Widget) -> Option<Self> {
+        Some(self)
+    }
+
+    // get and validate, falling back to the default
+    fn read<F>(data: &Data, name: &str, widget: &Widget, default: F) -> Self
+    where
+        F: FnOnce() -> Self,
+    {
+        Self::get(data, name).validate(widget).unwrap_or_else(|| {
+            log::warn!("bad value for {}, using default", name);
+            default()
+        })
+    }
+}
+
+impl Field for bool {
+    fn set_default(data: &mut Data, name: &str, value: &Self) {
+        data.set_default_bool(name, *value);
+    }
+
+    fn get(data: &Data, name: &str) -> Self {
+        data.get_bool(name)
+    }
+
+    fn set(data: &mut Data, name: &str, value: &Self) {
+        data.set_bool(name, *value);
+    }
+}
+
+impl Field for i64 {
+    fn set_default(data: &mut Data, name: &str, value: &Self) {
+        data.set_default_int(name, *value);
+    }
+
+    fn get(data: &Data, name: &str) -> Self {
+        data.get_int(name)
+    }
+
+    fn set(data: &mut Data, name: &str, value: &Self) {
+        data.set_int(name, *value);
+    }
+
+    fn validate(self, widget: &Widget) -> Option<Self> {
+        match *widget {
+            Widget::Int { min, max, .. } | Widget::IntSlider { min, max, .. } => {
+                Some(self.clamp(min as i64, max as i64))
+            }
+            _ => Some(self),
+        }
+    }
+}
+
+impl Field for f64 {
+    fn set_default(data: &mut Data, name: &str, value: &Self) {
+        data.set_default_double(name, *value);
+    }
+
+    fn get(data: &Data, name: &str) -> Self {
+        data.get_double(name)
+    }
+
+    fn set(data: &mut Data, name: &str, value: &Self) {
+        data.set_double(name, *value);
+    }
+
+    fn validate(self, widget: &Widget) -> Option<Self> {
+        if !self.is_finite() {
+            return None;
+        }
+        match *widget {
+            Widget::Float { min, max, .. } | Widget::FloatSlider { min, max, .. } => {
+                Some(self.clamp(min, max))
+            }
+            _ => Some(self),
+        }
+    }
+}
+
+impl Field for String {
+    fn set_default(data: &mut Data, name: &str, value: &Self) {
+        data.set_default_string(name, value);
+    }
+
+    fn get(data: &Data, name: &str) -> Self {
+        data.get_string(name).to_owned()
+    }
+
+    fn set(data: &mut Data, name: &str, value: &Self) {
+        data.set_string(name, value);
+    }
+
+    fn validate(self, widget: &Widget) -> Option<Self> {
+        match widget {
+            Widget::List(items) if !items.is_empty() => {
+                if items.iter().any(|(_, val)| *val == self) {
+                    Some(self)
+                } else {
+                    None
+                }
+            }
+            _ => Some(self),
+        }
+    }
+}
+
+// declare a settings struct, one line per setting:
+//
+//     bobs::settings! {
+//         pub struct Look {
+//             pub graticule: bool = true, "Draw graticule", Bool;
+//             pub line_width: i64 = 2, "Line width", IntSlider { min: 1, max: 10, step: 1 };
+//         }
+//     }
+//
+// the settings key is the field name, and widgets are variants of Widget.
+#[macro_export]
+macro_rules! settings {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$fattr:meta])*
+                $fvis:vis $field:ident : $ty:ty = $default:expr, $desc:expr, $widget:expr;
+            )*
+        }
+    ) => {
+        $(#[$attr])*
+        $vis struct $name {
+            $(
+                $(#[$fattr])*
+                $fvis $field: $ty,
+            )*
+        }
+
+        impl $crate::Settings for $name {
+            fn defaults(data: &mut $crate::Data) {
+                $(
+                    <$ty as $crate::Field>::set_default(data, stringify!($field), &$default);
+                )*
+            }
+
+            // the widget names are only in scope for the widget itself, so
+            // they can't shadow anything in $default or $desc
+            fn properties(props: &mut $crate::Properties) {
+                $(
+                    {
+                        #[allow(unused_imports)]
+                        use $crate::Widget::*;
+                        $widget
+                    }
+                    .add(props, stringify!($field), $desc);
+                )*
+            }
+
+            fn from_data(data: &$crate::Data) -> Self {
+                $name {
+                    $(
+                        $field: <$ty as $crate::Field>::read(
+                            data,
+                            stringify!($field),
+                            &{
+                                #[allow(unused_imports)]
+                                use $crate::Widget::*;
+                                $widget
+                            },
+                            || $default,
+                        ),
+                    )*
+                }
+            }
+
+            fn to_data(&self, data: &mut $crate::Data) {
+                $(
+                    <$ty as $crate::Field>::set(data, stringify!($field), &self.$field);
+                )*
+            }
+        }
+
+        impl ::std::default::Default for $name {
+            fn default() -> Self {
+                $name {
+                    $(
+                        $field: $default,
+                    )*
+                }
+            }
+        }
+    };
+}
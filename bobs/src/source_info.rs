@@ -1,20 +1,31 @@
-use crate::string::cstring;
-use crate::{ObsRawBox, ObsRawCounted};
-use std::ffi::{c_void, CString};
-use std::os::raw::c_char;
+use crate::string::{cstring, string_ref};
+use crate::{ObsRawBox, ObsRawCounted, Source};
+use std::ffi::c_void;
+use std::os::raw::{c_char, c_int};
 use std::ptr::NonNull;
 
+// where the settings version is stored, alongside the source's own settings
+pub const SETTINGS_VERSION: &str = "settings_version";
+
 pub trait SourceImpl: Sized {
     const ID: &'static str;
     const NAME: &'static str;
     const TYPE: SourceType = SourceType::Input;
     const ICON_TYPE: IconType = IconType::Unknown;
+    // bump this when the settings change shape, and upgrade in migrate
+    const VERSION: i64 = 0;
 
     // const fn restrictions need this to be a function
     fn output_flags() -> SourceFlags {
         SourceFlags::empty()
     }
 
+    // which of the optional callbacks below to register with OBS. rust
+    // can't tell which methods were overridden, so list them here.
+    fn callbacks() -> Callbacks {
+        Callbacks::empty()
+    }
+
     fn create(settings: &crate::Data, source: *mut obs_sys::obs_source_t) -> Self;
 
     fn get_properties(&mut self) -> Box<crate::Properties> {
@@ -23,6 +34,85 @@ pub trait SourceImpl: Sized {
     fn get_defaults(_settings: &mut crate::Data) {}
     fn update(&mut self, _settings: &crate::Data) {}
 
+    // upgrade settings saved by an older VERSION, before create sees them.
+    // settings from before versioning are version 0.
+    fn migrate(_settings: &mut crate::Data, _from: i64) {}
+
+    // Callbacks::ACTIVATE, shown on the program output
+    fn activate(&mut self) {}
+    fn deactivate(&mut self) {}
+    // Callbacks::SHOW, shown anywhere at all
+    fn show(&mut self) {}
+    fn hide(&mut self) {}
+
+    // Callbacks::VIDEO_TICK, once per frame with the seconds elapsed
+    fn video_tick(&mut self, _seconds: f32) {}
+    // Callbacks::VIDEO_RENDER, for synchronous video sources
+    fn video_render(&mut self) {}
+    // Callbacks::SIZE
+    fn get_width(&mut self) -> u32 {
+        0
+    }
+    fn get_height(&mut self) -> u32 {
+        0
+    }
+
+    // Callbacks::SAVE, to stash private state in the settings
+    fn save(&mut self, _settings: &mut crate::Data) {}
+    // Callbacks::LOAD
+    fn load(&mut self, _settings: &crate::Data) {}
+
+    // Callbacks::ENUM_SOURCES, for sources that render other sources
+    fn enum_active_sources(&mut self, _children: &mut SourceEnum) {}
+    fn enum_all_sources(&mut self, _children: &mut SourceEnum) {}
+
+    // Callbacks::MOUSE, needs SourceFlags::INTERACTION
+    fn mouse_click(
+        &mut self,
+        _event: &MouseEvent,
+        _button: MouseButton,
+        _mouse_up: bool,
+        _click_count: u32,
+    ) {
+    }
+    fn mouse_move(&mut self, _event: &MouseEvent, _mouse_leave: bool) {}
+    fn mouse_wheel(&mut self, _event: &MouseEvent, _x_delta: i32, _y_delta: i32) {}
+    fn focus(&mut self, _focus: bool) {}
+    // Callbacks::KEY, needs SourceFlags::INTERACTION
+    fn key_click(&mut self, _event: &KeyEvent, _key_up: bool) {}
+
+    // Callbacks::FILTER_VIDEO, return false to drop the frame
+    fn filter_video(&mut self, _frame: &mut obs_sys::obs_source_frame) -> bool {
+        true
+    }
+    // Callbacks::FILTER_AUDIO, return false to drop the audio
+    fn filter_audio(&mut self, _audio: &mut obs_sys::obs_audio_data) -> bool {
+        true
+    }
+
+    // Callbacks::MEDIA
+    fn media_play_pause(&mut self, _pause: bool) {}
+    fn media_restart(&mut self) {}
+    fn media_stop(&mut self) {}
+    fn media_next(&mut self) {}
+    fn media_previous(&mut self) {}
+    // in milliseconds
+    fn media_get_duration(&mut self) -> i64 {
+        0
+    }
+    fn media_get_time(&mut self) -> i64 {
+        0
+    }
+    fn media_set_time(&mut self, _milliseconds: i64) {}
+    fn media_get_state(&mut self) -> MediaState {
+        MediaState::None
+    }
+
+    // Callbacks::MISSING_FILES. ownership of the list passes to OBS.
+    fn missing_files(&mut self) -> *mut obs_sys::obs_missing_files_t {
+        std::ptr::null_mut()
+    }
+
     fn info() -> SourceInfo<Self> {
         SourceInfo::new()
     }
@@ -49,13 +139,14 @@ where
         }
     }
 
+    // obs keeps the id and name for as long as the module is loaded, so they
+    // are leaked. statics won't do, since those are shared by every T.
     fn new() -> Self {
-        static mut ID: Option<CString> = None;
-        let cid = unsafe { ID.get_or_insert_with(|| cstring(T::ID)).as_ptr() };
         SourceInfo {
             _marker: std::marker::PhantomData,
             raw: obs_sys::obs_source_info {
-                id: cid,
+                id: cstring(T::ID).into_raw(),
+                type_data: cstring(T::NAME).into_raw() as *mut c_void,
                 type_: T::TYPE.into_raw(),
                 output_flags: T::output_flags().into_raw(),
                 icon_type: T::ICON_TYPE.into_raw(),
@@ -70,22 +161,119 @@ where
                 ..Default::default()
             },
         }
+        .with_callbacks(T::callbacks())
     }
 
-    unsafe extern "C" fn get_name(_type_data: *mut c_void) -> *const c_char {
-        static mut NAME: Option<CString> = None;
-        NAME.get_or_insert_with(|| cstring(T::NAME)).as_ptr()
+    fn with_callbacks(mut self, callbacks: Callbacks) -> Self {
+        let raw = &mut self.raw;
+        if callbacks.contains(Callbacks::ACTIVATE) {
+            raw.activate = Some(Self::activate);
+            raw.deactivate = Some(Self::deactivate);
+        }
+        if callbacks.contains(Callbacks::SHOW) {
+            raw.show = Some(Self::show);
+            raw.hide = Some(Self::hide);
+        }
+        if callbacks.contains(Callbacks::VIDEO_TICK) {
+            raw.video_tick = Some(Self::video_tick);
+        }
+        if callbacks.contains(Callbacks::VIDEO_RENDER) {
+            raw.video_render = Some(Self::video_render);
+        }
+        if callbacks.contains(Callbacks::SIZE) {
+            raw.get_width = Some(Self::get_width);
+            raw.get_height = Some(Self::get_height);
+        }
+        if callbacks.contains(Callbacks::SAVE) {
+            raw.save = Some(Self::save);
+        }
+        if callbacks.contains(Callbacks::LOAD) {
+            raw.load = Some(Self::load);
+        }
+        if callbacks.contains(Callbacks::ENUM_SOURCES) {
+            raw.enum_active_sources = Some(Self::enum_active_sources);
+            raw.enum_all_sources = Some(Self::enum_all_sources);
+        }
+        if callbacks.contains(Callbacks::MOUSE) {
+            raw.mouse_click = Some(Self::mouse_click);
+            raw.mouse_move = Some(Self::mouse_move);
+            raw.mouse_wheel = Some(Self::mouse_wheel);
+            raw.focus = Some(Self::focus);
+        }
+        if callbacks.contains(Callbacks::KEY) {
+            raw.key_click = Some(Self::key_click);
+        }
+        if callbacks.contains(Callbacks::FILTER_VIDEO) {
+            raw.filter_video = Some(Self::filter_video);
+        }
+        if callbacks.contains(Callbacks::FILTER_AUDIO) {
+            raw.filter_audio = Some(Self::filter_audio);
+        }
+        if callbacks.contains(Callbacks::MEDIA) {
+            raw.media_play_pause = Some(Self::media_play_pause);
+            raw.media_restart = Some(Self::media_restart);
+            raw.media_stop = Some(Self::media_stop);
+            raw.media_next = Some(Self::media_next);
+            raw.media_previous = Some(Self::media_previous);
+            raw.media_get_duration = Some(Self::media_get_duration);
+            raw.media_get_time = Some(Self::media_get_time);
+            raw.media_set_time = Some(Self::media_set_time);
+            raw.media_get_state = Some(Self::media_get_state);
+        }
+        if callbacks.contains(Callbacks::MISSING_FILES) {
+            raw.missing_files = Some(Self::missing_files);
+        }
+        self
+    }
+
+    // borrow the source behind OBS's data pointer for the length of a call
+    unsafe fn with<R, F>(data: *mut c_void, f: F) -> R
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        let mut src = Box::from_raw(data as *mut T);
+        let r = f(&mut src);
+        std::mem::forget(src);
+        r
+    }
+
+    unsafe extern "C" fn get_name(type_data: *mut c_void) -> *const c_char {
+        type_data as *const c_char
     }
 
     unsafe extern "C" fn create(
         settings: *mut obs_sys::obs_data_t,
         source: *mut obs_sys::obs_source_t,
     ) -> *mut c_void {
-        let settings = crate::Data::from_raw_unowned(NonNull::new(settings).expect("null pointer"));
+        let mut settings =
+            crate::Data::from_raw_unowned(NonNull::new(settings).expect("null pointer"));
+        Self::migrate(&mut settings);
         let src = Box::new(T::create(&settings, source));
         Box::into_raw(src) as *mut c_void
     }
 
+    fn migrate(settings: &mut crate::Data) {
+        if T::VERSION == 0 {
+            return;
+        }
+        let from = if settings.has_user_value(SETTINGS_VERSION) {
+            settings.get_int(SETTINGS_VERSION)
+        } else if settings.items().any(|i| i.has_user_value()) {
+            0
+        } else {
+            // nothing saved at all, so this is a brand new source
+            T::VERSION
+        };
+        if from < T::VERSION {
+            log::info!("migrating {} settings from version {} to {}", T::ID, from, T::VERSION);
+            T::migrate(settings, from);
+        } else if from > T::VERSION {
+            log::warn!("{} settings are from a newer version ({})", T::ID, from);
+            return;
+        }
+        settings.set_int(SETTINGS_VERSION, T::VERSION);
+    }
+
     unsafe extern "C" fn destroy(data: *mut c_void) {
         let src = Box::from_raw(data as *mut T);
         std::mem::drop(src);
@@ -110,6 +298,184 @@ where
         src.update(&settings);
         std::mem::forget(src);
     }
+
+    unsafe extern "C" fn activate(data: *mut c_void) {
+        Self::with(data, |src| src.activate())
+    }
+
+    unsafe extern "C" fn deactivate(data: *mut c_void) {
+        Self::with(data, |src| src.deactivate())
+    }
+
+    unsafe extern "C" fn show(data: *mut c_void) {
+        Self::with(data, |src| src.show())
+    }
+
+    unsafe extern "C" fn hide(data: *mut c_void) {
+        Self::with(data, |src| src.hide())
+    }
+
+    unsafe extern "C" fn video_tick(data: *mut c_void, seconds: f32) {
+        Self::with(data, |src| src.video_tick(seconds))
+    }
+
+    unsafe extern "C" fn video_render(data: *mut c_void, _effect: *mut obs_sys::gs_effect_t) {
+        Self::with(data, |src| src.video_render())
+    }
+
+    unsafe extern "C" fn get_width(data: *mut c_void) -> u32 {
+        Self::with(data, |src| src.get_width())
+    }
+
+    unsafe extern "C" fn get_height(data: *mut c_void) -> u32 {
+        Self::with(data, |src| src.get_height())
+    }
+
+    unsafe extern "C" fn save(data: *mut c_void, settings: *mut obs_sys::obs_data_t) {
+        let mut settings =
+            crate::Data::from_raw_unowned(NonNull::new(settings).expect("null pointer"));
+        Self::with(data, |src| src.save(&mut settings))
+    }
+
+    unsafe extern "C" fn load(data: *mut c_void, settings: *mut obs_sys::obs_data_t) {
+        let settings = crate::Data::from_raw_unowned(NonNull::new(settings).expect("null pointer"));
+        Self::with(data, |src| src.load(&settings))
+    }
+
+    unsafe extern "C" fn enum_active_sources(
+        data: *mut c_void,
+        callback: obs_sys::obs_source_enum_proc_t,
+        param: *mut c_void,
+    ) {
+        let mut children = SourceEnum { callback, param };
+        Self::with(data, |src| src.enum_active_sources(&mut children))
+    }
+
+    unsafe extern "C" fn enum_all_sources(
+        data: *mut c_void,
+        callback: obs_sys::obs_source_enum_proc_t,
+        param: *mut c_void,
+    ) {
+        let mut children = SourceEnum { callback, param };
+        Self::with(data, |src| src.enum_all_sources(&mut children))
+    }
+
+    unsafe extern "C" fn mouse_click(
+        data: *mut c_void,
+        event: *const obs_sys::obs_mouse_event,
+        type_: i32,
+        mouse_up: bool,
+        click_count: u32,
+    ) {
+        let event = MouseEvent::from_raw(&*event);
+        if let Some(button) = MouseButton::from_raw(type_) {
+            Self::with(data, |src| {
+                src.mouse_click(&event, button, mouse_up, click_count)
+            })
+        }
+    }
+
+    unsafe extern "C" fn mouse_move(
+        data: *mut c_void,
+        event: *const obs_sys::obs_mouse_event,
+        mouse_leave: bool,
+    ) {
+        let event = MouseEvent::from_raw(&*event);
+        Self::with(data, |src| src.mouse_move(&event, mouse_leave))
+    }
+
+    unsafe extern "C" fn mouse_wheel(
+        data: *mut c_void,
+        event: *const obs_sys::obs_mouse_event,
+        x_delta: c_int,
+        y_delta: c_int,
+    ) {
+        let event = MouseEvent::from_raw(&*event);
+        Self::with(data, |src| src.mouse_wheel(&event, x_delta, y_delta))
+    }
+
+    unsafe extern "C" fn focus(data: *mut c_void, focus: bool) {
+        Self::with(data, |src| src.focus(focus))
+    }
+
+    unsafe extern "C" fn key_click(
+        data: *mut c_void,
+        event: *const obs_sys::obs_key_event,
+        key_up: bool,
+    ) {
+        let event = KeyEvent::from_raw(&*event);
+        Self::with(data, |src| src.key_click(&event, key_up))
+    }
+
+    unsafe extern "C" fn filter_video(
+        data: *mut c_void,
+        frame: *mut obs_sys::obs_source_frame,
+    ) -> *mut obs_sys::obs_source_frame {
+        let keep = match frame.as_mut() {
+            Some(f) => Self::with(data, |src| src.filter_video(f)),
+            None => false,
+        };
+        if keep {
+            frame
+        } else {
+            std::ptr::null_mut()
+        }
+    }
+
+    unsafe extern "C" fn filter_audio(
+        data: *mut c_void,
+        audio: *mut obs_sys::obs_audio_data,
+    ) -> *mut obs_sys::obs_audio_data {
+        let keep = match audio.as_mut() {
+            Some(a) => Self::with(data, |src| src.filter_audio(a)),
+            None => false,
+        };
+        if keep {
+            audio
+        } else {
+            std::ptr::null_mut()
+        }
+    }
+
+    unsafe extern "C" fn media_play_pause(data: *mut c_void, pause: bool) {
+        Self::with(data, |src| src.media_play_pause(pause))
+    }
+
+    unsafe extern "C" fn media_restart(data: *mut c_void) {
+        Self::with(data, |src| src.media_restart())
+    }
+
+    unsafe extern "C" fn media_stop(data: *mut c_void) {
+        Self::with(data, |src| src.media_stop())
+    }
+
+    unsafe extern "C" fn media_next(data: *mut c_void) {
+        Self::with(data, |src| src.media_next())
+    }
+
+    unsafe extern "C" fn media_previous(data: *mut c_void) {
+        Self::with(data, |src| src.media_previous())
+    }
+
+    unsafe extern "C" fn media_get_duration(data: *mut c_void) -> i64 {
+        Self::with(data, |src| src.media_get_duration())
+    }
+
+    unsafe extern "C" fn media_get_time(data: *mut c_void) -> i64 {
+        Self::with(data, |src| src.media_get_time())
+    }
+
+    unsafe extern "C" fn media_set_time(data: *mut c_void, milliseconds: i64) {
+        Self::with(data, |src| src.media_set_time(milliseconds))
+    }
+
+    unsafe extern "C" fn media_get_state(data: *mut c_void) -> obs_sys::obs_media_state {
+        Self::with(data, |src| src.media_get_state()).into_raw()
+    }
+
+    unsafe extern "C" fn missing_files(data: *mut c_void) -> *mut obs_sys::obs_missing_files_t {
+        Self::with(data, |src| src.missing_files())
+    }
 }
 
 impl<T> crate::Register for SourceInfo<T> {
@@ -232,3 +598,244 @@ impl IconType {
         }
     }
 }
+
+bitflags::bitflags! {
+    pub struct Callbacks: u32 {
+        const ACTIVATE = 1 << 0;
+        const SHOW = 1 << 1;
+        const VIDEO_TICK = 1 << 2;
+        const VIDEO_RENDER = 1 << 3;
+        const SIZE = 1 << 4;
+        const SAVE = 1 << 5;
+        const LOAD = 1 << 6;
+        const ENUM_SOURCES = 1 << 7;
+        const MOUSE = 1 << 8;
+        const KEY = 1 << 9;
+        const FILTER_VIDEO = 1 << 10;
+        const FILTER_AUDIO = 1 << 11;
+        const MEDIA = 1 << 12;
+        const MISSING_FILES = 1 << 13;
+    }
+}
+
+// hands child sources back to OBS from enum_active_sources/enum_all_sources
+#[derive(Debug)]
+pub struct SourceEnum {
+    callback: obs_sys::obs_source_enum_proc_t,
+    param: *mut c_void,
+}
+
+impl SourceEnum {
+    pub fn child(&mut self, parent: &Source, child: &Source) {
+        if let Some(callback) = self.callback {
+            unsafe {
+                callback(parent.as_raw().as_ptr(), child.as_raw().as_ptr(), self.param);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MouseEvent {
+    pub modifiers: u32,
+    pub x: i32,
+    pub y: i32,
+}
+
+impl MouseEvent {
+    pub fn from_raw(raw: &obs_sys::obs_mouse_event) -> Self {
+        MouseEvent {
+            modifiers: raw.modifiers,
+            x: raw.x,
+            y: raw.y,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+}
+
+impl MouseButton {
+    pub fn into_raw(self) -> obs_sys::obs_mouse_button_type {
+        match self {
+            MouseButton::Left => obs_sys::obs_mouse_button_type_MOUSE_LEFT,
+            MouseButton::Middle => obs_sys::obs_mouse_button_type_MOUSE_MIDDLE,
+            MouseButton::Right => obs_sys::obs_mouse_button_type_MOUSE_RIGHT,
+        }
+    }
+
+    pub fn from_raw(raw: obs_sys::obs_mouse_button_type) -> Option<Self> {
+        match raw {
+            obs_sys::obs_mouse_button_type_MOUSE_LEFT => Some(MouseButton::Left),
+            obs_sys::obs_mouse_button_type_MOUSE_MIDDLE => Some(MouseButton::Middle),
+            obs_sys::obs_mouse_button_type_MOUSE_RIGHT => Some(MouseButton::Right),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct KeyEvent {
+    pub modifiers: u32,
+    pub text: String,
+    pub native_modifiers: u32,
+    pub native_scancode: u32,
+    pub native_vkey: u32,
+}
+
+impl KeyEvent {
+    // the text pointer must be null or a valid C string
+    pub(crate) unsafe fn from_raw(raw: &obs_sys::obs_key_event) -> Self {
+        KeyEvent {
+            modifiers: raw.modifiers,
+            text: if raw.text.is_null() {
+                String::new()
+            } else {
+                string_ref(raw.text).to_owned()
+            },
+            native_modifiers: raw.native_modifiers,
+            native_scancode: raw.native_scancode,
+            native_vkey: raw.native_vkey,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MediaState {
+    None,
+    Playing,
+    Opening,
+    Buffering,
+    Paused,
+    Stopped,
+    Ended,
+    Error,
+}
+
+impl MediaState {
+    pub fn into_raw(self) -> obs_sys::obs_media_state {
+        match self {
+            MediaState::None => obs_sys::obs_media_state_OBS_MEDIA_STATE_NONE,
+            MediaState::Playing => obs_sys::obs_media_state_OBS_MEDIA_STATE_PLAYING,
+            MediaState::Opening => obs_sys::obs_media_state_OBS_MEDIA_STATE_OPENING,
+            MediaState::Buffering => obs_sys::obs_media_state_OBS_MEDIA_STATE_BUFFERING,
+            MediaState::Paused => obs_sys::obs_media_state_OBS_MEDIA_STATE_PAUSED,
+            MediaState::Stopped => obs_sys::obs_media_state_OBS_MEDIA_STATE_STOPPED,
+            MediaState::Ended => obs_sys::obs_media_state_OBS_MEDIA_STATE_ENDED,
+            MediaState::Error => obs_sys::obs_media_state_OBS_MEDIA_STATE_ERROR,
+        }
+    }
+
+    pub fn from_raw(raw: obs_sys::obs_media_state) -> Option<Self> {
+        match raw {
+            obs_sys::obs_media_state_OBS_MEDIA_STATE_NONE => Some(MediaState::None),
+            obs_sys::obs_media_state_OBS_MEDIA_STATE_PLAYING => Some(MediaState::Playing),
+            obs_sys::obs_media_state_OBS_MEDIA_STATE_OPENING => Some(MediaState::Opening),
+            obs_sys::obs_media_state_OBS_MEDIA_STATE_BUFFERING => Some(MediaState::Buffering),
+            obs_sys::obs_media_state_OBS_MEDIA_STATE_PAUSED => Some(MediaState::Paused),
+            obs_sys::obs_media_state_OBS_MEDIA_STATE_STOPPED => Some(MediaState::Stopped),
+            obs_sys::obs_media_state_OBS_MEDIA_STATE_ENDED => Some(MediaState::Ended),
+            obs_sys::obs_media_state_OBS_MEDIA_STATE_ERROR => Some(MediaState::Error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Data;
+
+    // records which version it was asked to migrate from
+    struct Versioned;
+
+    impl SourceImpl for Versioned {
+        const ID: &'static str = "versioned";
+        const NAME: &'static str = "Versioned";
+        const VERSION: i64 = 2;
+
+        fn create(_settings: &Data, _source: *mut obs_sys::obs_source_t) -> Self {
+            Versioned
+        }
+
+        fn migrate(settings: &mut Data, from: i64) {
+            settings.set_int("migrated_from", from);
+        }
+    }
+
+    struct Other;
+
+    impl SourceImpl for Other {
+        const ID: &'static str = "other";
+        const NAME: &'static str = "Other";
+
+        fn create(_settings: &Data, _source: *mut obs_sys::obs_source_t) -> Self {
+            Other
+        }
+    }
+
+    fn id_and_name<T: SourceImpl>() -> (&'static str, &'static str) {
+        unsafe {
+            let raw = T::info().into_raw();
+            let get_name = raw.get_name.unwrap();
+            (string_ref(raw.id), string_ref(get_name(raw.type_data)))
+        }
+    }
+
+    #[test]
+    fn each_source_has_its_own_id_and_name() {
+        assert_eq!(id_and_name::<Versioned>(), ("versioned", "Versioned"));
+        assert_eq!(id_and_name::<Other>(), ("other", "Other"));
+        // and the first one registered doesn't change after the second
+        assert_eq!(id_and_name::<Versioned>(), ("versioned", "Versioned"));
+    }
+
+    fn migrated(json: &str) -> Box<Data> {
+        let mut settings = Data::create_from_json(json).unwrap();
+        SourceInfo::<Versioned>::migrate(&mut settings);
+        settings
+    }
+
+    #[test]
+    fn unversioned_settings_are_version_0() {
+        let settings = migrated(r#"{"address": "scope:5555"}"#);
+        assert_eq!(settings.get_int("migrated_from"), 0);
+        assert_eq!(settings.get_int(SETTINGS_VERSION), 2);
+    }
+
+    #[test]
+    fn older_settings_migrate_from_their_version() {
+        let settings = migrated(r#"{"address": "scope:5555", "settings_version": 1}"#);
+        assert_eq!(settings.get_int("migrated_from"), 1);
+        assert_eq!(settings.get_int(SETTINGS_VERSION), 2);
+    }
+
+    #[test]
+    fn current_settings_are_left_alone() {
+        let settings = migrated(r#"{"address": "scope:5555", "settings_version": 2}"#);
+        assert!(!settings.has_user_value("migrated_from"));
+        assert_eq!(settings.get_int(SETTINGS_VERSION), 2);
+    }
+
+    #[test]
+    fn newer_settings_are_left_alone() {
+        let settings = migrated(r#"{"settings_version": 3}"#);
+        assert!(!settings.has_user_value("migrated_from"));
+        assert_eq!(settings.get_int(SETTINGS_VERSION), 3);
+    }
+
+    // nothing saved can't be told apart from a new source, so it gets the
+    // current defaults rather than whatever an old version defaulted to
+    #[test]
+    fn empty_settings_are_new() {
+        let settings = migrated("{}");
+        assert!(!settings.has_user_value("migrated_from"));
+        assert_eq!(settings.get_int(SETTINGS_VERSION), 2);
+    }
+}
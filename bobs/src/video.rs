@@ -0,0 +1,26 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VideoInfo {
+    pub fps_num: u32,
+    pub fps_den: u32,
+    pub base_width: u32,
+    pub base_height: u32,
+    pub output_width: u32,
+    pub output_height: u32,
+}
+
+pub fn get_video_info() -> Option<VideoInfo> {
+    unsafe {
+        let mut ovi: obs_sys::obs_video_info = std::mem::zeroed();
+        if !obs_sys::obs_get_video_info(&mut ovi) {
+            return None;
+        }
+        Some(VideoInfo {
+            fps_num: ovi.fps_num,
+            fps_den: ovi.fps_den,
+            base_width: ovi.base_width,
+            base_height: ovi.base_height,
+            output_width: ovi.output_width,
+            output_height: ovi.output_height,
+        })
+    }
+}
@@ -0,0 +1,225 @@
+use crate::capture::Frame;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Order {
+    Rgb,
+    Bgr,
+}
+
+// where the pixels are in a buffer of packed 24-bit rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Packed {
+    pub width: u32,
+    pub height: u32,
+    // where the first row starts
+    pub offset: usize,
+    // bytes from one row to the next, at least 3 * width
+    pub stride: usize,
+    pub order: Order,
+    // the first row is the bottom of the image, as in most BMPs
+    pub bottom_up: bool,
+}
+
+impl Packed {
+    // rows right after each other, top first
+    pub fn tight(width: u32, height: u32, order: Order) -> Self {
+        Packed {
+            width,
+            height,
+            offset: 0,
+            stride: 3 * width as usize,
+            order,
+            bottom_up: false,
+        }
+    }
+
+    // from a whole 24-bit uncompressed BMP file, like the scope sends
+    pub fn from_bmp(data: &[u8]) -> Option<Self> {
+        let u16_at = |i: usize| Some(u16::from_le_bytes([*data.get(i)?, *data.get(i + 1)?]));
+        let u32_at = |i: usize| {
+            let b = data.get(i..i + 4)?;
+            Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        };
+        if data.get(..2)? != b"BM" {
+            return None;
+        }
+        let offset = u32_at(10)? as usize;
+        let width = u32_at(18)? as i32;
+        let height = u32_at(22)? as i32;
+        let bpp = u16_at(28)?;
+        let compression = u32_at(30)?;
+        if bpp != 24 || compression != 0 || width <= 0 || height == 0 {
+            return None;
+        }
+
+        // rows are padded to 4 bytes, and a negative height means top-down
+        let width = width as u32;
+        let packed = Packed {
+            width,
+            height: height.unsigned_abs(),
+            offset,
+            stride: (3 * width as usize + 3) & !3,
+            order: Order::Bgr,
+            bottom_up: height > 0,
+        };
+        if data.len() < packed.len() {
+            return None;
+        }
+        Some(packed)
+    }
+
+    // the smallest buffer that holds every row
+    pub fn len(&self) -> usize {
+        if self.height == 0 {
+            return self.offset;
+        }
+        self.offset + self.stride * (self.height as usize - 1) + 3 * self.width as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    // the bytes of row y, counting from the top
+    fn row<'a>(&self, data: &'a [u8], y: u32) -> &'a [u8] {
+        let y = if self.bottom_up {
+            self.height - 1 - y
+        } else {
+            y
+        };
+        let start = self.offset + y as usize * self.stride;
+        &data[start..start + 3 * self.width as usize]
+    }
+}
+
+impl Frame {
+    // replace this frame with packed 24-bit pixels, or false if they don't fit
+    pub fn fill_packed(&mut self, data: &[u8], packed: &Packed) -> bool {
+        if packed.stride < 3 * packed.width as usize || data.len() < packed.len() {
+            return false;
+        }
+        self.resize(packed.width, packed.height);
+        let linesize = self.linesize() as usize;
+        if linesize == 0 {
+            return true;
+        }
+        for (y, dst) in self.data.chunks_exact_mut(linesize).enumerate() {
+            let src = packed.row(data, y as u32);
+            match packed.order {
+                Order::Rgb => expand::<0, 2>(src, dst),
+                Order::Bgr => expand::<2, 0>(src, dst),
+            }
+        }
+        true
+    }
+}
+
+// one row of 3-byte pixels into 4-byte RGBA. the channel positions are
+// constants, so the compiler can vectorize this.
+fn expand<const R: usize, const B: usize>(src: &[u8], dst: &mut [u8]) {
+    for (s, d) in src.chunks_exact(3).zip(dst.chunks_exact_mut(4)) {
+        d[0] = s[R];
+        d[1] = s[1];
+        d[2] = s[B];
+        d[3] = 0xff;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::screen_bmp;
+
+    fn pixel(x: u32, y: u32) -> [u8; 3] {
+        [(16 * x + y) as u8, 0x80 | y as u8, 0xf0 - x as u8]
+    }
+
+    fn assert_pixels(frame: &Frame, width: u32, height: u32) {
+        assert_eq!((frame.width, frame.height), (width, height));
+        for y in 0..height {
+            for x in 0..width {
+                let i = (4 * (y * width + x)) as usize;
+                let [r, g, b] = pixel(x, y);
+                assert_eq!(frame.data[i..i + 4], [r, g, b, 0xff], "at {}, {}", x, y);
+            }
+        }
+    }
+
+    // the same image, stored top row first with a negative height
+    fn top_down_bmp(width: u32, height: u32) -> Vec<u8> {
+        let mut bmp = screen_bmp(width, height, |x, y| pixel(x, height - 1 - y));
+        bmp[22..26].copy_from_slice(&(-(height as i32)).to_le_bytes());
+        bmp
+    }
+
+    #[test]
+    fn rows_are_padded_to_4_bytes() {
+        for &(width, stride) in &[(1, 4), (3, 12), (5, 16)] {
+            let bmp = screen_bmp(width, 3, pixel);
+            let packed = Packed::from_bmp(&bmp).unwrap();
+            assert_eq!(packed.stride, stride);
+            assert_eq!(packed.len(), bmp.len() - (stride - 3 * width as usize));
+
+            let mut frame = Frame::new(0, 0);
+            assert!(frame.fill_packed(&bmp, &packed));
+            assert_pixels(&frame, width, 3);
+        }
+    }
+
+    #[test]
+    fn bottom_up_and_top_down_agree() {
+        let bottom_up = screen_bmp(5, 3, pixel);
+        let top_down = top_down_bmp(5, 3);
+        assert_ne!(bottom_up, top_down);
+
+        for bmp in &[bottom_up, top_down] {
+            let packed = Packed::from_bmp(bmp).unwrap();
+            assert_eq!(packed.height, 3);
+            let mut frame = Frame::new(0, 0);
+            assert!(frame.fill_packed(bmp, &packed));
+            assert_pixels(&frame, 5, 3);
+        }
+    }
+
+    #[test]
+    fn order_swaps_red_and_blue() {
+        let data = [1, 2, 3, 4, 5, 6];
+        let mut frame = Frame::new(0, 0);
+        assert!(frame.fill_packed(&data, &Packed::tight(2, 1, Order::Rgb)));
+        assert_eq!(frame.data, [1, 2, 3, 0xff, 4, 5, 6, 0xff]);
+        assert!(frame.fill_packed(&data, &Packed::tight(2, 1, Order::Bgr)));
+        assert_eq!(frame.data, [3, 2, 1, 0xff, 6, 5, 4, 0xff]);
+    }
+
+    #[test]
+    fn truncated_buffers_are_refused() {
+        let bmp = screen_bmp(5, 3, pixel);
+        let packed = Packed::from_bmp(&bmp).unwrap();
+        let short = &bmp[..packed.len() - 1];
+        assert_eq!(Packed::from_bmp(short), None);
+
+        let mut frame = Frame::new(2, 2);
+        assert!(!frame.fill_packed(short, &packed));
+        // left as it was
+        assert_eq!(frame, Frame::new(2, 2));
+    }
+
+    #[test]
+    fn only_uncompressed_24_bit_bmps() {
+        let bmp = screen_bmp(5, 3, pixel);
+        let patched = |at: usize, value: &[u8]| {
+            let mut b = bmp.clone();
+            b[at..at + value.len()].copy_from_slice(value);
+            Packed::from_bmp(&b)
+        };
+        assert!(Packed::from_bmp(&bmp).is_some());
+        assert_eq!(patched(0, b"XX"), None);
+        assert_eq!(patched(28, &32u16.to_le_bytes()), None);
+        assert_eq!(patched(28, &8u16.to_le_bytes()), None);
+        // BI_RLE8 and BI_BITFIELDS
+        assert_eq!(patched(30, &1u32.to_le_bytes()), None);
+        assert_eq!(patched(30, &3u32.to_le_bytes()), None);
+        assert_eq!(patched(18, &0i32.to_le_bytes()), None);
+        assert_eq!(Packed::from_bmp(&bmp[..20]), None);
+    }
+}
@@ -0,0 +1,763 @@
+use crate::connection::{Backoff, Connection, State, Status};
+use crate::overlay;
+use crate::pacing::{Pacer, Pacing, Stats};
+use crate::scpi;
+use crate::trigger::Watch;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// longest to go without checking for messages while waiting
+const MESSAGE_POLL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    // tightly-packed RGBA, 4 * width bytes per line
+    pub data: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(width: u32, height: u32) -> Self {
+        Frame {
+            width,
+            height,
+            data: vec![0xff; (4 * width * height) as usize],
+        }
+    }
+
+    pub fn linesize(&self) -> u32 {
+        4 * self.width
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        self.data.resize((4 * width * height) as usize, 0xff);
+    }
+
+    pub fn fill_black(&mut self) {
+        for px in self.data.chunks_exact_mut(4) {
+            px.copy_from_slice(&[0, 0, 0, 0xff]);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Connected(String),
+    ConnectFailed(String),
+    Disconnected(String),
+}
+
+// where the capture engine sends its output
+pub trait FrameSink: Send {
+    fn frame(&mut self, frame: &Frame);
+    fn event(&mut self, _event: &Event) {}
+}
+
+// a sink that remembers everything it is sent, and when
+#[derive(Debug, Clone, Default)]
+pub struct Recorder {
+    inner: Arc<Mutex<Recording>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Recording {
+    pub frames: Vec<(Instant, Frame)>,
+    pub events: Vec<(Instant, Event)>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn recording(&self) -> Recording {
+        self.inner.lock().unwrap().clone()
+    }
+
+    pub fn frames(&self) -> Vec<Frame> {
+        let rec = self.inner.lock().unwrap();
+        rec.frames.iter().map(|(_, f)| f.clone()).collect()
+    }
+
+    pub fn events(&self) -> Vec<Event> {
+        let rec = self.inner.lock().unwrap();
+        rec.events.iter().map(|(_, e)| e.clone()).collect()
+    }
+
+    pub fn clear(&self) {
+        let mut rec = self.inner.lock().unwrap();
+        rec.frames.clear();
+        rec.events.clear();
+    }
+}
+
+impl FrameSink for Recorder {
+    fn frame(&mut self, frame: &Frame) {
+        let mut rec = self.inner.lock().unwrap();
+        rec.frames.push((Instant::now(), frame.clone()));
+    }
+
+    fn event(&mut self, event: &Event) {
+        let mut rec = self.inner.lock().unwrap();
+        rec.events.push((Instant::now(), event.clone()));
+    }
+}
+
+// what to show once the scope goes away
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Disconnect {
+    // keep showing the last frame
+    Hold,
+    Blank,
+    HoldThenBlank(Duration),
+    FadeToBlack(Duration),
+    // the status overlay, over the dimmed last frame
+    Overlay,
+}
+
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub address: String,
+    // serial number of a discovered scope, preferred over the address
+    pub serial: String,
+    pub on_disconnect: Disconnect,
+    pub backoff: Backoff,
+    pub connect_timeout: Duration,
+    pub pacing: Pacing,
+    // let go of the scope entirely while paused
+    pub disconnect_paused: bool,
+    pub trigger: Trigger,
+}
+
+// when a grab is worth doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Trigger {
+    // every time the pacer says so
+    Always,
+    // once after each single-shot acquisition, optionally pressing SINGLE
+    // again afterwards
+    Single { rearm: bool },
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            address: String::new(),
+            serial: String::new(),
+            on_disconnect: Disconnect::Hold,
+            backoff: Default::default(),
+            connect_timeout: Duration::from_millis(1000),
+            pacing: Default::default(),
+            disconnect_paused: false,
+            trigger: Trigger::Always,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Message<G> {
+    End,
+    Update(Settings, G),
+    // stop or start grabbing, when nobody's watching
+    Pause(bool),
+    Command(Command),
+}
+
+// front-panel buttons, pressed between grabs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Command {
+    Run,
+    Stop,
+    Single,
+    Clear,
+    Auto,
+}
+
+impl Command {
+    pub const ALL: [Command; 5] = [
+        Command::Run,
+        Command::Stop,
+        Command::Single,
+        Command::Clear,
+        Command::Auto,
+    ];
+
+    pub fn scpi(self) -> &'static str {
+        match self {
+            Command::Run => ":RUN",
+            Command::Stop => ":STOP",
+            Command::Single => ":SINGle",
+            Command::Clear => ":CLEar",
+            Command::Auto => ":AUToscale",
+        }
+    }
+}
+
+// turns a connected scope into frames
+pub trait Grabber: Send {
+    fn grab<'a>(
+        &'a mut self,
+        scope: &'a mut ds1054z::Scope,
+        frame: &'a mut Frame,
+    ) -> Pin<Box<dyn Future<Output = Result<(), scpi::Error>> + 'a>>;
+}
+
+pub struct Engine<S, G> {
+    settings: Settings,
+    conn: Connection,
+    pacer: Pacer,
+    stats: Arc<Mutex<Stats>>,
+    // the last frame grabbed, if it's still worth showing
+    frame: Frame,
+    has_frame: bool,
+    last_good: Instant,
+    // shown instead, when there's no scope
+    placeholder: Frame,
+    grabber: G,
+    sink: S,
+    paused: bool,
+    watch: Watch,
+}
+
+impl<S, G> Engine<S, G>
+where
+    S: FrameSink,
+    G: Grabber,
+{
+    pub fn new(sink: S, grabber: G) -> Self {
+        Engine {
+            settings: Default::default(),
+            conn: Connection::new(),
+            pacer: Pacer::default(),
+            stats: Default::default(),
+            frame: Frame::new(800, 480),
+            has_frame: false,
+            last_good: Instant::now(),
+            placeholder: Frame::new(800, 480),
+            grabber,
+            sink,
+            paused: false,
+            watch: Watch::default(),
+        }
+    }
+
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    pub fn state(&self) -> State {
+        self.conn.state()
+    }
+
+    pub fn status_handle(&self) -> Arc<Mutex<Status>> {
+        self.conn.status_handle()
+    }
+
+    pub fn stats_handle(&self) -> Arc<Mutex<Stats>> {
+        self.stats.clone()
+    }
+
+    pub fn update(&mut self, settings: Settings, grabber: G) {
+        let event = self.conn.configure(
+            &settings.address,
+            &settings.serial,
+            settings.backoff,
+            settings.connect_timeout,
+        );
+        self.emit(event);
+        if self.paused && settings.disconnect_paused {
+            let event = self.conn.pause();
+            self.emit(event);
+        }
+        self.has_frame = false;
+        self.last_good = Instant::now();
+        self.pacer.set_pacing(settings.pacing);
+        self.pacer.stalled();
+        self.watch = Watch::default();
+        self.settings = settings;
+        self.grabber = grabber;
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        if paused == self.paused {
+            return;
+        }
+        self.paused = paused;
+        self.pacer.stalled();
+        if paused && self.settings.disconnect_paused {
+            let event = self.conn.pause();
+            self.emit(event);
+        } else if !paused {
+            self.conn.resume();
+        }
+    }
+
+    fn emit(&mut self, event: Option<Event>) {
+        if let Some(e) = event {
+            self.sink.event(&e);
+        }
+    }
+
+    // one connect / grab / present cycle
+    pub async fn step(&mut self) {
+        // attempt to connect, if it's time
+        let event = self.conn.poll().await;
+        self.emit(event);
+
+        // grab a frame, if there's something new to see
+        if let Some(s) = self.conn.scope() {
+            let started = Instant::now();
+            let ready = match self.settings.trigger {
+                Trigger::Always => Ok(true),
+                Trigger::Single { rearm } => self.watch.poll(s, rearm).await,
+            };
+            let grabbed = match ready {
+                Ok(true) => self.grabber.grab(s, &mut self.frame).await.map(|()| true),
+                other => other,
+            };
+            match grabbed {
+                // still waiting for a trigger, so keep showing the last one
+                Ok(false) => self.last_good = Instant::now(),
+                Ok(true) => {
+                    self.conn.working();
+                    self.has_frame = true;
+                    self.last_good = Instant::now();
+                    self.pacer.grabbed(started);
+                    self.sink.frame(&self.frame);
+                }
+                Err(e) => {
+                    self.pacer.stalled();
+                    log::warn!("could not grab from {}: {}", self.settings.address, e);
+                    let event = self.conn.lost(e.to_string());
+                    self.emit(event);
+                }
+            }
+        } else {
+            self.disconnected();
+        }
+    }
+
+    // press a button on the scope, if there is one
+    async fn command(&mut self, command: Command) {
+        let scope = match self.conn.scope() {
+            Some(s) => s,
+            None => {
+                log::info!("not connected, dropping {}", command.scpi());
+                return;
+            }
+        };
+        self.watch.pressed(command);
+        if let Err(e) = scpi::send(scope, command.scpi()).await {
+            log::warn!(
+                "could not send {} to {}: {}",
+                command.scpi(),
+                self.settings.address,
+                e
+            );
+            let event = self.conn.lost(e.to_string());
+            self.emit(event);
+        }
+    }
+
+    // apply the disconnect policy
+    fn disconnected(&mut self) {
+        let since = self.last_good.elapsed();
+        // whatever is shown keeps the size the scope last had
+        self.placeholder.resize(self.frame.width, self.frame.height);
+        match self.settings.on_disconnect {
+            Disconnect::Hold => return,
+            Disconnect::HoldThenBlank(d) if self.has_frame && since < d => return,
+            Disconnect::FadeToBlack(d) if self.has_frame && since < d => {
+                self.placeholder.clone_from(&self.frame);
+                self.placeholder
+                    .dim(1.0 - since.as_secs_f64() / d.as_secs_f64());
+            }
+            Disconnect::Overlay => {
+                let last = if self.has_frame {
+                    Some(&self.frame)
+                } else {
+                    None
+                };
+                overlay::render(&mut self.placeholder, last, &self.conn.status());
+            }
+            Disconnect::Blank | Disconnect::HoldThenBlank(_) | Disconnect::FadeToBlack(_) => {
+                self.placeholder.fill_black();
+            }
+        }
+        self.sink.frame(&self.placeholder);
+    }
+
+    pub async fn run(mut self, channel: mpsc::Receiver<Message<G>>) {
+        loop {
+            let started = Instant::now();
+            let until = if self.paused {
+                started + MESSAGE_POLL
+            } else {
+                self.step().await;
+                self.pacer.next(started)
+            };
+            *self.stats.lock().unwrap() = self.pacer.stats();
+
+            // don't busy-loop
+            if !self.wait(until, &channel).await {
+                return;
+            }
+        }
+    }
+
+    // handle messages until `until`, or return false if told to end
+    async fn wait(&mut self, until: Instant, channel: &mpsc::Receiver<Message<G>>) -> bool {
+        loop {
+            match channel.try_recv() {
+                Ok(Message::End) => return false,
+                Ok(Message::Update(s, g)) => self.update(s, g),
+                Ok(Message::Command(c)) => self.command(c).await,
+                Ok(Message::Pause(p)) => {
+                    self.set_paused(p);
+                    // start grabbing again right away
+                    if !p {
+                        return true;
+                    }
+                }
+                Err(mpsc::TryRecvError::Disconnected) => return false,
+                Err(mpsc::TryRecvError::Empty) => (),
+            }
+
+            let now = Instant::now();
+            if now >= until {
+                return true;
+            }
+            smol::Timer::after((until - now).min(MESSAGE_POLL)).await;
+        }
+    }
+}
+
+// an engine running on its own thread, shut down on drop
+#[derive(Debug)]
+pub struct Worker<G> {
+    channel: mpsc::Sender<Message<G>>,
+    status: Arc<Mutex<Status>>,
+    stats: Arc<Mutex<Stats>>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl<G> Worker<G>
+where
+    G: Grabber + 'static,
+{
+    pub fn spawn<S>(sink: S, grabber: G) -> Self
+    where
+        S: FrameSink + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        let engine = Engine::new(sink, grabber);
+        let status = engine.status_handle();
+        let stats = engine.stats_handle();
+        let thread = std::thread::spawn(move || smol::block_on(engine.run(rx)));
+        Worker {
+            channel: tx,
+            status,
+            stats,
+            thread: Some(thread),
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        self.status.lock().unwrap().clone()
+    }
+
+    pub fn stats(&self) -> Stats {
+        *self.stats.lock().unwrap()
+    }
+
+    pub fn update(&self, settings: Settings, grabber: G) {
+        self.channel
+            .send(Message::Update(settings, grabber))
+            .expect("could not update settings");
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.channel
+            .send(Message::Pause(paused))
+            .expect("could not pause");
+    }
+
+    pub fn remote(&self) -> Remote<G> {
+        Remote {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+// sends commands to a worker from other threads, like the hotkey thread
+#[derive(Debug, Clone)]
+pub struct Remote<G> {
+    channel: mpsc::Sender<Message<G>>,
+}
+
+impl<G> Remote<G> {
+    // false if the worker is gone
+    pub fn command(&self, command: Command) -> bool {
+        self.channel.send(Message::Command(command)).is_ok()
+    }
+}
+
+impl<G> Drop for Worker<G> {
+    fn drop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            // send end-thread message
+            self.channel
+                .send(Message::End)
+                .expect("could not end thread");
+            thread.join().expect("could not join thread");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockScope;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    // fills frames with the number of grabs so far, or fails when told to
+    #[derive(Debug, Clone, Default)]
+    struct Fake {
+        grabs: Arc<AtomicUsize>,
+        fail: Arc<AtomicBool>,
+        // how long each grab takes
+        latency: Duration,
+        // when each grab started
+        started: Arc<Mutex<Vec<Instant>>>,
+    }
+
+    impl Grabber for Fake {
+        fn grab<'a>(
+            &'a mut self,
+            _scope: &'a mut ds1054z::Scope,
+            frame: &'a mut Frame,
+        ) -> Pin<Box<dyn Future<Output = Result<(), scpi::Error>> + 'a>> {
+            Box::pin(async move {
+                self.started.lock().unwrap().push(Instant::now());
+                smol::Timer::after(self.latency).await;
+                if self.fail.load(Ordering::SeqCst) {
+                    return Err(scpi::Error::Timeout);
+                }
+                let n = self.grabs.fetch_add(1, Ordering::SeqCst) + 1;
+                frame.resize(160, 90);
+                for px in frame.data.chunks_exact_mut(4) {
+                    px.copy_from_slice(&[n as u8, 0x40, 0x80, 0xff]);
+                }
+                Ok(())
+            })
+        }
+    }
+
+    fn settings(mock: &MockScope, on_disconnect: Disconnect) -> Settings {
+        Settings {
+            address: mock.address(),
+            on_disconnect,
+            // stay disconnected for the rest of the test
+            backoff: Backoff {
+                initial: Duration::from_secs(10),
+                jitter: 0.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn engine(mock: &MockScope, on_disconnect: Disconnect) -> (Engine<Recorder, Fake>, Fake) {
+        let fake = Fake::default();
+        let mut engine = Engine::new(Recorder::new(), fake.clone());
+        engine.update(settings(mock, on_disconnect), fake.clone());
+        (engine, fake)
+    }
+
+    // grab one frame, then lose the scope
+    fn grab_then_lose(engine: &mut Engine<Recorder, Fake>, fake: &Fake) -> Frame {
+        smol::block_on(engine.step());
+        fake.fail.store(true, Ordering::SeqCst);
+        smol::block_on(engine.step());
+        assert_eq!(engine.state(), State::Backoff);
+        let frames = engine.sink().frames();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(
+            engine.sink().events().last(),
+            Some(&Event::Disconnected(engine.settings.address.clone()))
+        );
+        engine.sink().clear();
+        frames[0].clone()
+    }
+
+    fn is_black(frame: &Frame) -> bool {
+        frame.data.chunks_exact(4).all(|px| px == [0, 0, 0, 0xff])
+    }
+
+    #[test]
+    fn grabs_go_to_the_sink() {
+        let mock = MockScope::start().unwrap();
+        let (mut engine, _fake) = engine(&mock, Disconnect::Hold);
+        for _ in 0..3 {
+            smol::block_on(engine.step());
+        }
+
+        assert_eq!(engine.state(), State::Connected);
+        assert_eq!(
+            engine.sink().events(),
+            vec![Event::Connected(mock.address())]
+        );
+        let counts: Vec<u8> = engine.sink().frames().iter().map(|f| f.data[0]).collect();
+        assert_eq!(counts, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn hold_sends_nothing_more() {
+        let mock = MockScope::start().unwrap();
+        let (mut engine, fake) = engine(&mock, Disconnect::Hold);
+        grab_then_lose(&mut engine, &fake);
+        for _ in 0..3 {
+            smol::block_on(engine.step());
+        }
+
+        assert!(engine.sink().frames().is_empty());
+        // no reconnect yet, so nothing to report either
+        assert!(engine.sink().events().is_empty());
+    }
+
+    #[test]
+    fn blank_goes_black_right_away() {
+        let mock = MockScope::start().unwrap();
+        let (mut engine, fake) = engine(&mock, Disconnect::Blank);
+        let last = grab_then_lose(&mut engine, &fake);
+        smol::block_on(engine.step());
+
+        let frames = engine.sink().frames();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(
+            (frames[0].width, frames[0].height),
+            (last.width, last.height)
+        );
+        assert!(is_black(&frames[0]));
+    }
+
+    #[test]
+    fn hold_then_blank_waits() {
+        let mock = MockScope::start().unwrap();
+        let wait = Duration::from_millis(100);
+        let (mut engine, fake) = engine(&mock, Disconnect::HoldThenBlank(wait));
+        let last = grab_then_lose(&mut engine, &fake);
+        smol::block_on(engine.step());
+        assert!(engine.sink().frames().is_empty());
+
+        std::thread::sleep(wait);
+        smol::block_on(engine.step());
+        let frames = engine.sink().frames();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(
+            (frames[0].width, frames[0].height),
+            (last.width, last.height)
+        );
+        assert!(is_black(&frames[0]));
+    }
+
+    #[test]
+    fn fade_to_black_dims_the_last_frame() {
+        let mock = MockScope::start().unwrap();
+        let fade = Duration::from_millis(200);
+        let (mut engine, fake) = engine(&mock, Disconnect::FadeToBlack(fade));
+        let last = grab_then_lose(&mut engine, &fake);
+        smol::block_on(engine.step());
+        std::thread::sleep(fade / 2);
+        smol::block_on(engine.step());
+        std::thread::sleep(fade / 2);
+        smol::block_on(engine.step());
+
+        let frames = engine.sink().frames();
+        assert_eq!(frames.len(), 3);
+        let green: Vec<u8> = frames.iter().map(|f| f.data[1]).collect();
+        assert!(
+            green[0] <= last.data[1] && green[0] > green[1],
+            "{:?}",
+            green
+        );
+        assert!(green[1] > 0, "{:?}", green);
+        assert!(is_black(&frames[2]));
+        for frame in &frames {
+            assert_eq!((frame.width, frame.height), (last.width, last.height));
+        }
+    }
+
+    #[test]
+    fn overlay_shows_the_status_over_the_last_frame() {
+        let mock = MockScope::start().unwrap();
+        let (mut engine, fake) = engine(&mock, Disconnect::Overlay);
+        let last = grab_then_lose(&mut engine, &fake);
+        smol::block_on(engine.step());
+
+        let frames = engine.sink().frames();
+        assert_eq!(frames.len(), 1);
+        let shown = &frames[0];
+        assert_eq!((shown.width, shown.height), (last.width, last.height));
+        // dimmed in the corner, with the status box across the middle
+        assert!(shown.data[1] < last.data[1]);
+        assert_ne!(shown, &last);
+        assert!(!is_black(shown));
+    }
+
+    // the gaps between grabs from a worker running for a while
+    fn intervals(pacing: Pacing, latency: Duration) -> Vec<Duration> {
+        let mock = MockScope::start().unwrap();
+        let fake = Fake {
+            latency,
+            ..Default::default()
+        };
+        let worker = Worker::spawn(Recorder::new(), fake.clone());
+        let settings = Settings {
+            pacing,
+            ..settings(&mock, Disconnect::Hold)
+        };
+        worker.update(settings, fake.clone());
+        std::thread::sleep(Duration::from_millis(600));
+        drop(worker);
+
+        let times = fake.started.lock().unwrap().clone();
+        assert!(times.len() > 3, "{} grabs", times.len());
+        times.windows(2).map(|w| w[1] - w[0]).collect()
+    }
+
+    // never faster than `period`, and not much slower on average
+    fn assert_paced(gaps: &[Duration], period: Duration) {
+        let mean = gaps.iter().sum::<Duration>() / gaps.len() as u32;
+        for gap in gaps {
+            assert!(*gap >= period * 9 / 10, "{:?} between grabs", gap);
+        }
+        assert!(mean < period * 3 / 2, "{:?} between grabs on average", mean);
+    }
+
+    #[test]
+    fn pacing_holds_the_frame_rate() {
+        let pacing = Pacing {
+            fps: 20.0,
+            adaptive: false,
+        };
+        let gaps = intervals(pacing, Duration::from_millis(5));
+        assert_paced(&gaps, Duration::from_millis(50));
+    }
+
+    #[test]
+    fn adaptive_pacing_slows_down_for_the_scope() {
+        let pacing = Pacing {
+            fps: 50.0,
+            adaptive: true,
+        };
+        // 60 ms grabs leave half as long again between them, once measured
+        let gaps = intervals(pacing, Duration::from_millis(60));
+        assert_paced(&gaps[1..], Duration::from_millis(90));
+    }
+}
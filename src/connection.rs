@@ -0,0 +1,414 @@
+use crate::capture::Event;
+use crate::discovery;
+use smol_timeout::TimeoutExt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum State {
+    // no address to connect to
+    Idle,
+    Connecting,
+    Connected,
+    // waiting to retry after a failure
+    Backoff,
+    // gave up after too many attempts
+    Failed,
+    // disconnected on purpose, while nobody is watching
+    Paused,
+}
+
+impl std::fmt::Display for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let s = match self {
+            State::Idle => "idle",
+            State::Connecting => "connecting",
+            State::Connected => "connected",
+            State::Backoff => "waiting to retry",
+            State::Failed => "failed",
+            State::Paused => "paused",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    pub multiplier: f64,
+    // fraction of each delay to randomly shave off, 0.0 to 1.0
+    pub jitter: f64,
+    // give up after this many attempts in a row, or 0 for never
+    pub max_attempts: u32,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: 0.2,
+            max_attempts: 0,
+        }
+    }
+}
+
+impl Backoff {
+    // delay before retry number `attempt`, where r is uniform in [0, 1)
+    pub fn delay(&self, attempt: u32, r: f64) -> Duration {
+        let base = self.initial.as_secs_f64() * self.multiplier.powi(attempt as i32);
+        let capped = base.min(self.max.as_secs_f64());
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        Duration::from_secs_f64(capped * (1.0 - jitter * r))
+    }
+}
+
+// what the connection is up to, shared with whoever wants to display it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Status {
+    pub address: String,
+    pub state: State,
+    pub attempts: u32,
+    pub retry_in: Option<Duration>,
+    pub last_error: Option<String>,
+}
+
+impl Default for Status {
+    fn default() -> Self {
+        Status {
+            address: String::new(),
+            state: State::Idle,
+            attempts: 0,
+            retry_in: None,
+            last_error: None,
+        }
+    }
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.state {
+            State::Idle => write!(f, "no address set")?,
+            State::Connecting => write!(f, "connecting to {}", self.address)?,
+            State::Connected => write!(f, "connected to {}", self.address)?,
+            State::Backoff => {
+                write!(f, "retrying {}", self.address)?;
+                if let Some(d) = self.retry_in {
+                    write!(f, " in {:.1}s", d.as_secs_f64())?;
+                }
+            }
+            State::Failed => write!(
+                f,
+                "gave up on {} after {} attempts",
+                self.address, self.attempts
+            )?,
+            State::Paused => write!(f, "paused, not talking to {}", self.address)?,
+        }
+        if self.state != State::Connected {
+            if let Some(ref e) = self.last_error {
+                write!(f, " ({})", e)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Status {
+    // a short line saying what's going on, for the status overlay
+    pub fn headline(&self) -> String {
+        match self.state {
+            State::Idle => "No oscilloscope address set".to_owned(),
+            State::Connecting | State::Backoff => format!("Connecting to {}…", self.address),
+            State::Connected => format!("Connected to {}", self.address),
+            State::Failed => format!("Gave up on {}", self.address),
+            State::Paused => "Paused".to_owned(),
+        }
+    }
+
+    // the last error, in words a viewer might understand
+    pub fn reason(&self) -> Option<String> {
+        let e = self.last_error.as_ref()?;
+        let lower = e.to_lowercase();
+        let friendly = if lower.contains("refused") {
+            "Connection refused"
+        } else if lower.contains("timed out") || lower.contains("timedout") {
+            "Timed out"
+        } else if lower.contains("reset") {
+            "Connection reset"
+        } else if lower.contains("unreachable") {
+            "Network unreachable"
+        } else if lower.contains("lookup") || lower.contains("resolve") {
+            "Address not found"
+        } else {
+            return Some(e.clone());
+        };
+        Some(friendly.to_owned())
+    }
+}
+
+// owns the scope connection and decides when to (re)connect
+pub struct Connection {
+    address: String,
+    // if set, find the scope by serial number and only fall back to `manual`
+    serial: String,
+    manual: String,
+    backoff: Backoff,
+    timeout: Duration,
+    scope: Option<ds1054z::Scope>,
+    state: State,
+    attempts: u32,
+    retry_at: Instant,
+    last_error: Option<String>,
+    status: Arc<Mutex<Status>>,
+    rng: u64,
+}
+
+impl Connection {
+    pub fn new() -> Self {
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Connection {
+            address: String::new(),
+            serial: String::new(),
+            manual: String::new(),
+            backoff: Default::default(),
+            timeout: Duration::from_millis(1000),
+            scope: None,
+            state: State::Idle,
+            attempts: 0,
+            retry_at: Instant::now(),
+            last_error: None,
+            status: Default::default(),
+            rng: seed | 1,
+        }
+    }
+
+    pub fn status_handle(&self) -> Arc<Mutex<Status>> {
+        self.status.clone()
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    // forget any existing connection and start over
+    pub fn configure(
+        &mut self,
+        address: &str,
+        serial: &str,
+        backoff: Backoff,
+        timeout: Duration,
+    ) -> Option<Event> {
+        let event = self.close();
+        // until it's found, the serial is the best name we have
+        self.address = if address.is_empty() { serial } else { address }.to_owned();
+        self.manual = address.to_owned();
+        self.serial = serial.to_owned();
+        self.backoff = backoff;
+        self.timeout = timeout;
+        self.attempts = 0;
+        self.last_error = None;
+        self.retry_at = Instant::now();
+        self.set_state(if address.is_empty() && serial.is_empty() {
+            State::Idle
+        } else {
+            State::Backoff
+        });
+        event
+    }
+
+    pub fn scope(&mut self) -> Option<&mut ds1054z::Scope> {
+        self.scope.as_mut()
+    }
+
+    // connect, if it's time to
+    pub async fn poll(&mut self) -> Option<Event> {
+        if self.state != State::Backoff || Instant::now() < self.retry_at {
+            self.publish();
+            return None;
+        }
+
+        self.set_state(State::Connecting);
+        let result = if self.serial.is_empty() {
+            self.connect().await
+        } else {
+            self.find_and_connect().await
+        };
+        match result {
+            Ok(s) => {
+                log::info!("connected to {}", self.address);
+                self.scope = Some(s);
+                // attempts are only reset once the scope answers, so one
+                // that drops every connection right away still backs off
+                self.last_error = None;
+                self.set_state(State::Connected);
+                Some(Event::Connected(self.address.clone()))
+            }
+            Err(e) => Some(self.connect_failed(e)),
+        }
+    }
+
+    async fn connect(&self) -> Result<ds1054z::Scope, String> {
+        match ds1054z::Scope::connect(&self.address)
+            .timeout(self.timeout)
+            .await
+        {
+            Some(Ok(s)) => Ok(s),
+            Some(Err(e)) => Err(format!("{:?}", e)),
+            None => Err("timed out".to_owned()),
+        }
+    }
+
+    // try where the scope was last seen, since browsing takes a while
+    async fn find_and_connect(&mut self) -> Result<ds1054z::Scope, String> {
+        if let Some(found) = discovery::lookup(&self.serial) {
+            self.address = found.address;
+            match self.connect().await {
+                Ok(s) => return Ok(s),
+                Err(e) => log::info!("{} is not at {}: {}", self.serial, self.address, e),
+            }
+        }
+
+        // look again, in case DHCP moved it
+        let serial = self.serial.clone();
+        let found = smol::unblock(move || discovery::resolve(&serial)).await;
+        self.address = found.unwrap_or_else(|| self.manual.clone());
+        if self.address.is_empty() {
+            return Err(format!("{} not found", self.serial));
+        }
+        self.connect().await
+    }
+
+    // let go of the scope until resume
+    pub fn pause(&mut self) -> Option<Event> {
+        if matches!(self.state, State::Idle | State::Failed) {
+            return None;
+        }
+        let event = self.close();
+        self.set_state(State::Paused);
+        event
+    }
+
+    // reconnect right away, if paused
+    pub fn resume(&mut self) {
+        if self.state == State::Paused {
+            self.attempts = 0;
+            self.retry_at = Instant::now();
+            self.set_state(State::Backoff);
+        }
+    }
+
+    // the scope answered, so the next failure starts the backoff over
+    pub fn working(&mut self) {
+        self.attempts = 0;
+    }
+
+    // the scope stopped answering, so drop it and back off
+    pub fn lost(&mut self, error: String) -> Option<Event> {
+        let event = self.close();
+        self.retry_or_fail(error);
+        event
+    }
+
+    fn close(&mut self) -> Option<Event> {
+        self.scope.take().map(|_| {
+            log::info!("disconnected from {}", self.address);
+            Event::Disconnected(self.address.clone())
+        })
+    }
+
+    fn connect_failed(&mut self, error: String) -> Event {
+        self.retry_or_fail(error.clone());
+        Event::ConnectFailed(error)
+    }
+
+    // count a failure, then back off or give up
+    fn retry_or_fail(&mut self, error: String) {
+        self.attempts += 1;
+        if self.backoff.max_attempts > 0 && self.attempts >= self.backoff.max_attempts {
+            log::warn!("giving up on {}: {}", self.address, error);
+            self.last_error = Some(error);
+            self.set_state(State::Failed);
+        } else {
+            self.last_error = Some(error);
+            self.schedule_retry();
+        }
+    }
+
+    fn schedule_retry(&mut self) {
+        let r = self.random();
+        let delay = self.backoff.delay(self.attempts.saturating_sub(1), r);
+        self.retry_at = Instant::now() + delay;
+        self.set_state(State::Backoff);
+    }
+
+    fn set_state(&mut self, state: State) {
+        self.state = state;
+        self.publish();
+    }
+
+    pub fn status(&self) -> Status {
+        Status {
+            address: self.address.clone(),
+            state: self.state,
+            attempts: self.attempts,
+            retry_in: if self.state == State::Backoff {
+                Some(self.retry_at.saturating_duration_since(Instant::now()))
+            } else {
+                None
+            },
+            last_error: self.last_error.clone(),
+        }
+    }
+
+    fn publish(&self) {
+        *self.status.lock().unwrap() = self.status();
+    }
+
+    // xorshift, good enough for jitter
+    fn random(&mut self) -> f64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl Default for Connection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discovery::Browser;
+    use crate::mock::{MockScope, Responder, IDN};
+
+    #[test]
+    fn serial_connects_where_last_seen() {
+        let mock = MockScope::start().unwrap();
+        let responder = Responder::start(&mock).unwrap();
+        let serial = IDN.split(',').nth(2).unwrap();
+        assert_eq!(responder.browser().browse().len(), 1);
+
+        let mut conn = Connection::new();
+        conn.configure("", serial, Backoff::default(), Duration::from_millis(500));
+        let started = Instant::now();
+        let event = smol::block_on(conn.poll());
+
+        assert_eq!(event, Some(Event::Connected(mock.address())));
+        assert_eq!(conn.state(), State::Connected);
+        // a browse would have waited this long for answers
+        assert!(started.elapsed() < Browser::default().timeout);
+    }
+}
@@ -0,0 +1,291 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// where Rigol scopes listen for raw SCPI
+pub const RAW_PORT: u16 = 5555;
+
+const MDNS: ([u8; 4], u16) = ([224, 0, 0, 251], 5353);
+const PORTMAP: ([u8; 4], u16) = ([255, 255, 255, 255], 111);
+const SERVICE: &str = "_scpi-raw._tcp.local";
+
+// ONC RPC numbers for asking a portmapper where VXI-11 lives
+const PMAP_PROG: u32 = 100_000;
+const PMAP_VERS: u32 = 2;
+const PMAP_GETPORT: u32 = 3;
+const VXI11_CORE: u32 = 0x0607af;
+const IPPROTO_TCP: u32 = 6;
+
+const DNS_PTR: u16 = 12;
+const DNS_SRV: u16 = 33;
+
+// everything found so far, across all sources
+static FOUND: Mutex<Vec<Instrument>> = Mutex::new(Vec::new());
+static BROWSING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instrument {
+    pub address: String,
+    pub manufacturer: String,
+    pub model: String,
+    pub serial: String,
+    pub version: String,
+}
+
+impl Instrument {
+    // from a *IDN? reply, "manufacturer,model,serial,version"
+    pub fn from_idn(address: String, idn: &str) -> Option<Self> {
+        let mut fields = idn.trim().splitn(4, ',').map(|f| f.trim().to_owned());
+        let instrument = Instrument {
+            address,
+            manufacturer: fields.next()?,
+            model: fields.next()?,
+            serial: fields.next()?,
+            version: fields.next().unwrap_or_default(),
+        };
+        if instrument.serial.is_empty() {
+            return None;
+        }
+        Some(instrument)
+    }
+}
+
+impl std::fmt::Display for Instrument {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {} ({})", self.model, self.serial, self.address)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Browser {
+    pub mdns: SocketAddr,
+    pub portmap: SocketAddr,
+    // port to probe on hosts that only answered the portmapper
+    pub raw_port: u16,
+    // how long to wait for answers, and for each probe
+    pub timeout: Duration,
+}
+
+impl Default for Browser {
+    fn default() -> Self {
+        Browser {
+            mdns: (Ipv4Addr::from(MDNS.0), MDNS.1).into(),
+            portmap: (Ipv4Addr::from(PORTMAP.0), PORTMAP.1).into(),
+            raw_port: RAW_PORT,
+            timeout: Duration::from_millis(500),
+        }
+    }
+}
+
+impl Browser {
+    // look for instruments and ask each who it is. this blocks for a while.
+    pub fn browse(&self) -> Vec<Instrument> {
+        let mut candidates = vec![];
+        if let Err(e) = self.query_mdns(&mut candidates) {
+            log::debug!("mdns browse failed: {}", e);
+        }
+        if let Err(e) = self.query_portmap(&mut candidates) {
+            log::debug!("vxi-11 browse failed: {}", e);
+        }
+        candidates.sort();
+        candidates.dedup();
+
+        let found: Vec<_> = candidates
+            .into_iter()
+            .filter_map(|addr| match probe(addr, self.timeout) {
+                Ok(i) => i,
+                Err(e) => {
+                    log::debug!("no answer from {}: {}", addr, e);
+                    None
+                }
+            })
+            .collect();
+        remember(&found);
+        found
+    }
+
+    fn query_mdns(&self, candidates: &mut Vec<SocketAddr>) -> std::io::Result<()> {
+        // from an ephemeral port, so responders answer us directly
+        let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+        let mut query = vec![0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+        for label in SERVICE.split('.') {
+            query.push(label.len() as u8);
+            query.extend_from_slice(label.as_bytes());
+        }
+        query.extend_from_slice(&[0, 0, DNS_PTR as u8, 0, 1]);
+        socket.send_to(&query, self.mdns)?;
+
+        collect(&socket, self.timeout, |reply, from| {
+            // answers come from the instrument itself, so only the port is needed
+            let port = parse_srv_port(reply).unwrap_or(self.raw_port);
+            candidates.push(SocketAddr::new(from.ip(), port));
+        })
+    }
+
+    fn query_portmap(&self, candidates: &mut Vec<SocketAddr>) -> std::io::Result<()> {
+        let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+        socket.set_broadcast(true)?;
+        let xid = std::process::id();
+        let call = [
+            xid,
+            0, // CALL
+            2, // RPC version
+            PMAP_PROG,
+            PMAP_VERS,
+            PMAP_GETPORT,
+            0, // AUTH_NULL credentials
+            0,
+            0, // AUTH_NULL verifier
+            0,
+            VXI11_CORE,
+            1,
+            IPPROTO_TCP,
+            0,
+        ];
+        let call: Vec<u8> = call.iter().flat_map(|w| w.to_be_bytes()).collect();
+        socket.send_to(&call, self.portmap)?;
+
+        collect(&socket, self.timeout, |reply, from| {
+            let words: Vec<u32> = reply
+                .chunks_exact(4)
+                .map(|w| u32::from_be_bytes([w[0], w[1], w[2], w[3]]))
+                .collect();
+            // xid, REPLY, MSG_ACCEPTED, verifier, SUCCESS, port
+            if let [x, 1, 0, _, _, 0, port, ..] = words[..] {
+                if x == xid && port != 0 {
+                    candidates.push(SocketAddr::new(from.ip(), self.raw_port));
+                }
+            }
+        })
+    }
+}
+
+// everything any browse has found
+pub fn found() -> Vec<Instrument> {
+    FOUND.lock().unwrap().clone()
+}
+
+pub fn lookup(serial: &str) -> Option<Instrument> {
+    FOUND
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|i| i.serial == serial)
+        .cloned()
+}
+
+// browse in the background, unless that's already happening
+pub fn refresh() {
+    if BROWSING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    std::thread::spawn(|| {
+        Browser::default().browse();
+        BROWSING.store(false, Ordering::SeqCst);
+    });
+}
+
+// refresh, then wait up to `timeout` for the browse to finish. false if it's
+// still going, in which case found() only has what was seen so far.
+pub fn refresh_and_wait(timeout: Duration) -> bool {
+    refresh();
+    let deadline = Instant::now() + timeout;
+    while BROWSING.load(Ordering::SeqCst) {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    true
+}
+
+// browse for the current address of a scope by serial. this blocks too, so
+// try lookup first.
+pub fn resolve(serial: &str) -> Option<String> {
+    let found = Browser::default().browse();
+    found
+        .into_iter()
+        .find(|i| i.serial == serial)
+        .map(|i| i.address)
+}
+
+fn remember(found: &[Instrument]) {
+    let mut all = FOUND.lock().unwrap();
+    for instrument in found {
+        all.retain(|i| i.serial != instrument.serial);
+        all.push(instrument.clone());
+    }
+    all.sort_by(|a, b| (&a.model, &a.serial).cmp(&(&b.model, &b.serial)));
+}
+
+fn collect<F>(socket: &UdpSocket, timeout: Duration, mut f: F) -> std::io::Result<()>
+where
+    F: FnMut(&[u8], SocketAddr),
+{
+    let deadline = Instant::now() + timeout;
+    let mut buf = [0; 1500];
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left == Duration::from_secs(0) {
+            return Ok(());
+        }
+        socket.set_read_timeout(Some(left))?;
+        match socket.recv_from(&mut buf) {
+            Ok((n, from)) => f(&buf[..n], from),
+            Err(e)
+                if e.kind() == std::io::ErrorKind::WouldBlock
+                    || e.kind() == std::io::ErrorKind::TimedOut =>
+            {
+                return Ok(())
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// the port from the first SRV record in a DNS reply, if any
+fn parse_srv_port(msg: &[u8]) -> Option<u16> {
+    let u16_at = |i: usize| Some(u16::from_be_bytes([*msg.get(i)?, *msg.get(i + 1)?]));
+    let questions = u16_at(4)?;
+    let records = u16_at(6)? as usize + u16_at(8)? as usize + u16_at(10)? as usize;
+    let mut i = 12;
+    for _ in 0..questions {
+        i = skip_name(msg, i)? + 4;
+    }
+    for _ in 0..records {
+        i = skip_name(msg, i)?;
+        let kind = u16_at(i)?;
+        let len = u16_at(i + 8)? as usize;
+        let data = i + 10;
+        if kind == DNS_SRV {
+            // priority, weight, port, target
+            return u16_at(data + 4);
+        }
+        i = data + len;
+    }
+    None
+}
+
+fn skip_name(msg: &[u8], mut i: usize) -> Option<usize> {
+    loop {
+        let len = *msg.get(i)?;
+        match len {
+            0 => return Some(i + 1),
+            // compression pointer, always last
+            l if l & 0xc0 == 0xc0 => return Some(i + 2),
+            l => i += 1 + l as usize,
+        }
+    }
+}
+
+fn probe(address: SocketAddr, timeout: Duration) -> std::io::Result<Option<Instrument>> {
+    let mut stream = TcpStream::connect_timeout(&address, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    stream.write_all(b"*IDN?\n")?;
+    let mut idn = String::new();
+    BufReader::new(stream).read_line(&mut idn)?;
+    Ok(Instrument::from_idn(address.to_string(), &idn))
+}
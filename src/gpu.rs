@@ -0,0 +1,248 @@
+use crate::capture::{Frame, FrameSink};
+use crate::render::Color;
+use crate::source::{migrate_settings, set_defaults, ScopeSource, View, SETTINGS_VERSION};
+use bobs::Settings as _;
+use std::sync::{Arc, Mutex};
+
+bobs::settings! {
+    // how the uploaded screen is drawn
+    #[derive(Debug, Clone)]
+    pub struct Draw {
+        pub scale_filter: String = String::from("point"), "Scaling", List(&[
+            ("Sharp", "point"),
+            ("Smooth", "linear"),
+            ("Anisotropic", "anisotropic"),
+        ]);
+        pub tint: i64 = Color::WHITE.to_obs(), "Tint", Color;
+    }
+}
+
+// the default effect's Draw, with every texel multiplied by `color`
+const TINT_EFFECT: &str = r#"
+uniform float4x4 ViewProj;
+uniform texture2d image;
+uniform float4 color = {1.0, 1.0, 1.0, 1.0};
+
+sampler_state def_sampler {
+    Filter   = Linear;
+    AddressU = Clamp;
+    AddressV = Clamp;
+};
+
+struct VertInOut {
+    float4 pos : POSITION;
+    float2 uv  : TEXCOORD0;
+};
+
+VertInOut VSDefault(VertInOut vert_in)
+{
+    VertInOut vert_out;
+    vert_out.pos = mul(float4(vert_in.pos.xyz, 1.0), ViewProj);
+    vert_out.uv  = vert_in.uv;
+    return vert_out;
+}
+
+float4 PSDrawMultiply(VertInOut vert_in) : TARGET
+{
+    return image.Sample(def_sampler, vert_in.uv) * color;
+}
+
+technique DrawMultiply
+{
+    pass
+    {
+        vertex_shader = VSDefault(vert_in);
+        pixel_shader  = PSDrawMultiply(vert_in);
+    }
+}
+"#;
+
+// the screen source, but drawn by the render thread instead of pushed as
+// async frames, so each grab is uploaded exactly once
+#[derive(Debug)]
+pub struct GpuScopeSource {
+    scope: ScopeSource<TextureSink>,
+    latest: Arc<Mutex<Latest>>,
+    texture: Option<Box<bobs::Texture>>,
+    sampler: Option<Box<bobs::Sampler>>,
+    effect: Option<Box<bobs::Effect>>,
+    filter: bobs::ScaleFilter,
+    tint: Color,
+}
+
+// the newest composed frame, waiting for the render thread
+#[derive(Debug)]
+struct Latest {
+    frame: Frame,
+    fresh: bool,
+}
+
+#[derive(Debug)]
+pub struct TextureSink {
+    latest: Arc<Mutex<Latest>>,
+}
+
+impl FrameSink for TextureSink {
+    fn frame(&mut self, frame: &Frame) {
+        let mut latest = self.latest.lock().unwrap();
+        latest.frame.clone_from(frame);
+        latest.fresh = true;
+    }
+}
+
+impl bobs::SourceImpl for GpuScopeSource {
+    const ID: &'static str = "ds1054z_gpu";
+    const NAME: &'static str = "Rigol DS1054Z (GPU)";
+    const ICON_TYPE: bobs::IconType = bobs::IconType::WindowCapture;
+    const VERSION: i64 = SETTINGS_VERSION;
+
+    fn output_flags() -> bobs::SourceFlags {
+        bobs::SourceFlags::VIDEO | bobs::SourceFlags::CUSTOM_DRAW
+    }
+
+    fn callbacks() -> bobs::Callbacks {
+        bobs::Callbacks::ACTIVATE
+            | bobs::Callbacks::SHOW
+            | bobs::Callbacks::VIDEO_RENDER
+            | bobs::Callbacks::SIZE
+    }
+
+    fn create(settings: &bobs::Data, source: *mut obs_sys::obs_source_t) -> Self {
+        let latest = Arc::new(Mutex::new(Latest {
+            frame: Frame::new(0, 0),
+            fresh: false,
+        }));
+        let sink = TextureSink {
+            latest: latest.clone(),
+        };
+        let mut src = GpuScopeSource {
+            scope: ScopeSource::new(settings, source, sink),
+            latest,
+            texture: None,
+            sampler: None,
+            effect: None,
+            filter: bobs::ScaleFilter::Point,
+            tint: Color::WHITE,
+        };
+        src.configure(settings);
+        src
+    }
+
+    fn get_properties(&mut self) -> Box<bobs::Properties> {
+        let mut props = self.scope.properties();
+        Draw::properties(&mut props);
+        props
+    }
+
+    fn get_defaults(settings: &mut bobs::Data) {
+        set_defaults(settings);
+        View::defaults(settings);
+        Draw::defaults(settings);
+    }
+
+    fn migrate(settings: &mut bobs::Data, from: i64) {
+        migrate_settings(settings, from);
+    }
+
+    fn update(&mut self, settings: &bobs::Data) {
+        self.scope.configure(settings);
+        self.configure(settings);
+    }
+
+    fn activate(&mut self) {
+        self.scope.set_active(true);
+    }
+
+    fn deactivate(&mut self) {
+        self.scope.set_active(false);
+    }
+
+    fn show(&mut self) {
+        self.scope.set_showing(true);
+    }
+
+    fn hide(&mut self) {
+        self.scope.set_showing(false);
+    }
+
+    fn video_render(&mut self) {
+        self.upload();
+        if self.sampler.is_none() {
+            self.sampler = bobs::Sampler::create(self.filter);
+        }
+        if self.effect.is_none() {
+            self.effect = bobs::Effect::create(TINT_EFFECT, "ds1054z tint");
+        }
+        let (texture, effect) = match (&self.texture, &self.effect) {
+            (Some(t), Some(e)) => (t, e),
+            _ => return,
+        };
+
+        let image = match effect.param("image") {
+            Some(p) => p,
+            None => return,
+        };
+        if let Some(ref sampler) = self.sampler {
+            image.set_next_sampler(sampler);
+        }
+        image.set_texture(texture);
+        if let Some(color) = effect.param("color") {
+            let c = |v: u8| v as f32 / 255.0;
+            let t = self.tint;
+            color.set_vec4([c(t.r), c(t.g), c(t.b), c(t.a)]);
+        }
+
+        effect.draw("DrawMultiply", || texture.draw(0, 0));
+    }
+
+    fn get_width(&mut self) -> u32 {
+        self.texture.as_ref().map(|t| t.width()).unwrap_or(0)
+    }
+
+    fn get_height(&mut self) -> u32 {
+        self.texture.as_ref().map(|t| t.height()).unwrap_or(0)
+    }
+}
+
+impl GpuScopeSource {
+    fn configure(&mut self, settings: &bobs::Data) {
+        let draw = Draw::from_data(settings);
+        let filter = match draw.scale_filter.as_str() {
+            "linear" => bobs::ScaleFilter::Linear,
+            "anisotropic" => bobs::ScaleFilter::Anisotropic,
+            _ => bobs::ScaleFilter::Point,
+        };
+        if filter != self.filter {
+            // made again on the next render
+            self.sampler = None;
+            self.filter = filter;
+        }
+        self.tint = Color::from_obs(draw.tint);
+    }
+
+    // copy a new frame to the GPU, if there is one
+    fn upload(&mut self) {
+        let mut latest = self.latest.lock().unwrap();
+        if !latest.fresh {
+            return;
+        }
+        latest.fresh = false;
+        let frame = &latest.frame;
+        if frame.width == 0 || frame.height == 0 {
+            return;
+        }
+
+        let same_size = self
+            .texture
+            .as_ref()
+            .map(|t| (t.width(), t.height()) == (frame.width, frame.height))
+            .unwrap_or(false);
+        if !same_size {
+            self.texture =
+                bobs::Texture::create_dynamic(frame.width, frame.height, bobs::ColorFormat::Rgba);
+        }
+        if let Some(ref mut texture) = self.texture {
+            texture.set_image(&frame.data, frame.linesize(), false);
+        }
+    }
+}
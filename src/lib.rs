@@ -1,2 +1,19 @@
+pub mod bitmap;
+pub mod capture;
+pub mod connection;
+pub mod discovery;
+pub mod font;
+pub mod gpu;
+pub mod measure;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
 pub mod module;
+pub mod overlay;
+pub mod pacing;
+pub mod render;
+pub mod scpi;
 pub mod source;
+pub mod template;
+pub mod tile;
+pub mod trigger;
+pub mod waveform;
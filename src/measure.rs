@@ -0,0 +1,540 @@
+use crate::capture::{Frame, Grabber, Worker};
+use crate::font;
+use crate::render::Color;
+use crate::scpi;
+use crate::source::{
+    add_properties, migrate_settings, read_settings, register_hotkeys, set_defaults, show,
+    status_line, ObsSink, Remotes, Visibility, ADDRESS_HELP, SETTINGS_VERSION,
+};
+use crate::template::Template;
+use bobs::Settings as _;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+// what the scope answers when it can't measure something
+const INVALID: f64 = 9.9e37;
+
+// empty space around the text, in font pixels
+const MARGIN: u32 = 2;
+
+// the checkboxes a template takes the place of
+const FIXED_LAYOUT: [&str; 10] = [
+    "chan1", "chan2", "chan3", "chan4", "vpp", "vrms", "freq", "period", "duty", "rise",
+];
+
+bobs::settings! {
+    #[derive(Debug, Clone)]
+    pub struct Readout {
+        pub chan1: bool = true, "Channel 1", Bool;
+        pub chan2: bool = false, "Channel 2", Bool;
+        pub chan3: bool = false, "Channel 3", Bool;
+        pub chan4: bool = false, "Channel 4", Bool;
+        pub vpp: bool = true, "Peak-to-peak voltage", Bool;
+        pub vrms: bool = false, "RMS voltage", Bool;
+        pub freq: bool = true, "Frequency", Bool;
+        pub period: bool = false, "Period", Bool;
+        pub duty: bool = false, "Duty cycle", Bool;
+        pub rise: bool = false, "Rise time", Bool;
+        pub template: String = String::new(), "Template", Text(bobs::TextType::Multiline);
+        pub digits: i64 = 3, "Significant digits", Int { min: 1, max: 6, step: 1 };
+        pub text_size: i64 = 4, "Text size", IntSlider { min: 1, max: 16, step: 1 };
+        pub text_color: i64 = Color::WHITE.to_obs(), "Text color", Color;
+        pub background: i64 = Color::BLACK.to_obs(), "Background color", Color;
+        pub background_opacity: i64 = 60,
+            "Background opacity (%)", IntSlider { min: 0, max: 100, step: 1 };
+    }
+}
+
+// something :MEASure:ITEM? can measure
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Item {
+    Vpp,
+    Vrms,
+    Frequency,
+    Period,
+    Duty,
+    RiseTime,
+}
+
+impl Item {
+    pub const ALL: [Item; 6] = [
+        Item::Vpp,
+        Item::Vrms,
+        Item::Frequency,
+        Item::Period,
+        Item::Duty,
+        Item::RiseTime,
+    ];
+
+    pub fn scpi(self) -> &'static str {
+        match self {
+            Item::Vpp => "VPP",
+            Item::Vrms => "VRMS",
+            Item::Frequency => "FREQ",
+            Item::Period => "PER",
+            Item::Duty => "PDUT",
+            Item::RiseTime => "RTIM",
+        }
+    }
+
+    // by SCPI name or label, in any case
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_uppercase();
+        Item::ALL
+            .iter()
+            .find(|i| i.scpi() == name || i.label().to_uppercase() == name)
+            .copied()
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Item::Vpp => "Vpp",
+            Item::Vrms => "Vrms",
+            Item::Frequency => "Freq",
+            Item::Period => "Period",
+            Item::Duty => "Duty",
+            Item::RiseTime => "Rise",
+        }
+    }
+
+    // a measured value, ready to show
+    pub fn format(self, value: Option<f64>, digits: usize) -> String {
+        match (self, value) {
+            // the scope reports duty as a fraction
+            (Item::Duty, Some(v)) => format!("{:.*} %", digits.saturating_sub(2), 100.0 * v),
+            (Item::Duty, None) => "---- %".to_owned(),
+            (_, v) => engineering(v, self.unit(), digits),
+        }
+    }
+
+    pub fn unit(self) -> &'static str {
+        match self {
+            Item::Vpp | Item::Vrms => "V",
+            Item::Frequency => "Hz",
+            Item::Period | Item::RiseTime => "s",
+            Item::Duty => "%",
+        }
+    }
+}
+
+// a value with an SI prefix, like "1.23 kHz", or dashes if there's no value
+pub fn engineering(value: Option<f64>, unit: &str, digits: usize) -> String {
+    let digits = digits.max(1) as i32;
+    let value = match value.filter(|v| v.is_finite()) {
+        Some(v) if v != 0.0 => v,
+        Some(_) => return format!("{:.*} {}", digits as usize - 1, 0.0, unit),
+        None => return format!("---- {}", unit),
+    };
+
+    // round first, so 999.96 becomes 1.00 k and not 1000 without a prefix
+    let magnitude = value.abs().log10().floor() as i32;
+    let step = 10f64.powi(magnitude - digits + 1);
+    let value = (value / step).round() * step;
+    let magnitude = value.abs().log10().floor() as i32;
+
+    let exponent = prefix_exponent(magnitude);
+    let decimals = (digits - 1 - (magnitude - exponent)).max(0) as usize;
+    with_prefix(value, exponent, decimals, unit)
+}
+
+// the exponent of the SI prefix for a value whose leading digit is at
+// 10^magnitude, from -12 to 9
+pub fn prefix_exponent(magnitude: i32) -> i32 {
+    (magnitude.div_euclid(3) * 3).clamp(-12, 9)
+}
+
+// a value scaled to the SI prefix for 10^exponent, like "1.234 kHz"
+pub fn with_prefix(value: f64, exponent: i32, decimals: usize, unit: &str) -> String {
+    const PREFIXES: [&str; 8] = ["p", "n", "µ", "m", "", "k", "M", "G"];
+    let exponent = exponent.clamp(-12, 9).div_euclid(3) * 3;
+    let prefix = PREFIXES[(exponent / 3 + 4) as usize];
+    format!(
+        "{:.*} {}{}",
+        decimals,
+        value / 10f64.powi(exponent),
+        prefix,
+        unit
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Measurement {
+    pub channel: usize,
+    pub item: Item,
+    // None if the scope couldn't measure it
+    pub value: Option<f64>,
+}
+
+#[derive(Debug)]
+pub struct MeasureSource {
+    // unregistered when dropped
+    _hotkeys: Vec<bobs::Hotkey>,
+    worker: Worker<Meter>,
+    serial: String,
+    visibility: Visibility,
+}
+
+// queries measurements and draws them as text
+#[derive(Debug, Clone)]
+pub struct Meter {
+    pub channels: Vec<usize>,
+    pub items: Vec<Item>,
+    // shown instead of channels and items, if set
+    pub template: Option<Template>,
+    pub digits: usize,
+    pub scale: u32,
+    pub color: Color,
+    pub background: Color,
+}
+
+impl bobs::SourceImpl for MeasureSource {
+    const ID: &'static str = "ds1054z_measure";
+    const NAME: &'static str = "Rigol DS1054Z Measurements";
+    const ICON_TYPE: bobs::IconType = bobs::IconType::Text;
+    const VERSION: i64 = SETTINGS_VERSION;
+
+    fn output_flags() -> bobs::SourceFlags {
+        bobs::SourceFlags::ASYNC_VIDEO
+    }
+
+    fn callbacks() -> bobs::Callbacks {
+        bobs::Callbacks::ACTIVATE | bobs::Callbacks::SHOW
+    }
+
+    fn create(settings: &bobs::Data, source: *mut obs_sys::obs_source_t) -> Self {
+        let worker = Worker::spawn(ObsSink::new(source), Meter::default());
+        let remotes: Remotes<Meter> = Arc::new(Mutex::new(vec![worker.remote()]));
+        let mut src = MeasureSource {
+            _hotkeys: register_hotkeys(source, &remotes),
+            worker,
+            serial: String::new(),
+            visibility: Visibility::default(),
+        };
+        src.update(settings);
+        src
+    }
+
+    fn get_properties(&mut self) -> Box<bobs::Properties> {
+        let mut props = bobs::Properties::create();
+        add_properties(
+            &mut props,
+            &self.serial,
+            ADDRESS_HELP,
+            &status_line(&self.worker),
+        );
+        Readout::properties(&mut props);
+        if let Some(mut template) = props.get("template") {
+            template.set_long_description(
+                "Replaces the channels and measurements above. {CH1.VPP} shows channel 1's \
+                 peak-to-peak voltage, and {CH2.FREQ:.2k} shows channel 2's frequency in kHz \
+                 with two decimals. Measurements are VPP, VRMS, FREQ, PER, PDUT and RTIM.",
+            );
+        }
+        props
+            .add_text("template_error", "", bobs::TextType::Default)
+            .set_enabled(false);
+        props.set_modified_callback("template", |props, settings| {
+            let template = settings.get_string("template");
+            let error = Template::parse(template).err();
+            if let (Some(e), Some(mut p)) = (&error, props.get("template_error")) {
+                p.set_description(&format!("Template error: {}", e));
+            }
+            show(props, &["template_error"], error.is_some());
+            show(props, &FIXED_LAYOUT, template.trim().is_empty());
+            true
+        });
+        props
+    }
+
+    fn get_defaults(settings: &mut bobs::Data) {
+        set_defaults(settings);
+        Readout::defaults(settings);
+        // a few queries per channel, so don't hammer the scope by default
+        settings.set_default_int("fps", 2);
+    }
+
+    fn migrate(settings: &mut bobs::Data, from: i64) {
+        migrate_settings(settings, from);
+    }
+
+    fn update(&mut self, settings: &bobs::Data) {
+        let readout = Readout::from_data(settings);
+        let meter = Meter::from_readout(&readout);
+        self.visibility.configure(settings);
+        let settings = read_settings(settings);
+        self.serial = settings.serial.clone();
+        self.worker.update(settings, meter);
+        self.worker.set_paused(self.visibility.paused());
+    }
+
+    fn activate(&mut self) {
+        self.worker.set_paused(self.visibility.set_active(true));
+    }
+
+    fn deactivate(&mut self) {
+        self.worker.set_paused(self.visibility.set_active(false));
+    }
+
+    fn show(&mut self) {
+        self.worker.set_paused(self.visibility.set_showing(true));
+    }
+
+    fn hide(&mut self) {
+        self.worker.set_paused(self.visibility.set_showing(false));
+    }
+}
+
+impl Default for Meter {
+    fn default() -> Self {
+        Meter::from_readout(&Readout::default())
+    }
+}
+
+impl Meter {
+    pub fn from_readout(readout: &Readout) -> Self {
+        let channels = [readout.chan1, readout.chan2, readout.chan3, readout.chan4];
+        let items = [
+            readout.vpp,
+            readout.vrms,
+            readout.freq,
+            readout.period,
+            readout.duty,
+            readout.rise,
+        ];
+        let opacity = readout.background_opacity.clamp(0, 100) as u32;
+        Meter {
+            channels: (1..=4).filter(|c| channels[c - 1]).collect(),
+            items: Item::ALL
+                .iter()
+                .zip(&items)
+                .filter(|(_, on)| **on)
+                .map(|(item, _)| *item)
+                .collect(),
+            // a bad template is reported in the properties
+            template: Template::parse(&readout.template)
+                .ok()
+                .filter(|t| !t.is_empty()),
+            digits: readout.digits.max(1) as usize,
+            scale: readout.text_size.max(1) as u32,
+            color: Color::from_obs(readout.text_color).with_alpha(0xff),
+            background: Color::from_obs(readout.background).with_alpha((opacity * 255 / 100) as u8),
+        }
+    }
+
+    // every channel and item that will be shown, each once
+    pub fn wanted(&self) -> Vec<(usize, Item)> {
+        match self.template {
+            Some(ref t) => t.wanted(),
+            None => self
+                .channels
+                .iter()
+                .flat_map(|&c| self.items.iter().map(move |&i| (c, i)))
+                .collect(),
+        }
+    }
+
+    pub async fn fetch(&self, scope: &mut ds1054z::Scope) -> Result<Vec<Measurement>, scpi::Error> {
+        let mut measurements = vec![];
+        for (channel, item) in self.wanted() {
+            let query = format!(":MEAS:ITEM? {},CHAN{}", item.scpi(), channel);
+            let value = scpi::query_f64(scope, &query).await?;
+            measurements.push(Measurement {
+                channel,
+                item,
+                value: Some(value).filter(|v| v.abs() < INVALID),
+            });
+        }
+        Ok(measurements)
+    }
+
+    // the template's lines, or one line per channel, like
+    // "CH1  Vpp 2.00 V  Freq 1.00 kHz"
+    pub fn lines(&self, measurements: &[Measurement]) -> Vec<String> {
+        if let Some(ref t) = self.template {
+            return t
+                .render(measurements, self.digits)
+                .lines()
+                .map(str::to_owned)
+                .collect();
+        }
+        self.channels
+            .iter()
+            .map(|&channel| {
+                let mut line = format!("CH{}", channel);
+                for m in measurements.iter().filter(|m| m.channel == channel) {
+                    line += &format!(
+                        "  {} {}",
+                        m.item.label(),
+                        m.item.format(m.value, self.digits)
+                    );
+                }
+                line
+            })
+            .collect()
+    }
+
+    pub fn render(&self, lines: &[String], frame: &mut Frame) {
+        let widest = lines.iter().map(|l| font::text_width(l)).max().unwrap_or(0);
+        let line = font::HEIGHT + font::SPACING;
+        let width = (widest + 2 * MARGIN) * self.scale;
+        let height = (line * lines.len() as u32 + 2 * MARGIN) * self.scale;
+        frame.resize(width, height);
+        frame.fill_transparent(self.background);
+
+        let margin = (MARGIN * self.scale) as i32;
+        for (i, text) in lines.iter().enumerate() {
+            let y = margin + (i as u32 * line * self.scale) as i32;
+            frame.text(margin, y, self.scale, self.color, text);
+        }
+    }
+
+    async fn grab_measurements(
+        &mut self,
+        scope: &mut ds1054z::Scope,
+        frame: &mut Frame,
+    ) -> Result<(), scpi::Error> {
+        let measurements = self.fetch(scope).await?;
+        self.render(&self.lines(&measurements), frame);
+        Ok(())
+    }
+}
+
+impl Grabber for Meter {
+    fn grab<'a>(
+        &'a mut self,
+        scope: &'a mut ds1054z::Scope,
+        frame: &'a mut Frame,
+    ) -> Pin<Box<dyn Future<Output = Result<(), scpi::Error>> + 'a>> {
+        Box::pin(self.grab_measurements(scope, frame))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockScope;
+
+    #[test]
+    fn engineering_picks_a_prefix() {
+        assert_eq!(engineering(Some(1234.0), "Hz", 3), "1.23 kHz");
+        assert_eq!(engineering(Some(0.0123), "V", 3), "12.3 mV");
+        assert_eq!(engineering(Some(5e-9), "s", 2), "5.0 ns");
+        assert_eq!(engineering(Some(42.0), "V", 1), "40 V");
+    }
+
+    #[test]
+    fn engineering_rounds_into_the_next_prefix() {
+        assert_eq!(engineering(Some(999.96), "", 3), "1.00 k");
+        assert_eq!(engineering(Some(999.4), "Hz", 3), "999 Hz");
+    }
+
+    #[test]
+    fn engineering_zero_and_negatives() {
+        assert_eq!(engineering(Some(0.0), "V", 3), "0.00 V");
+        assert_eq!(engineering(Some(-0.0123), "V", 3), "-12.3 mV");
+        assert_eq!(engineering(Some(-999.96), "Hz", 3), "-1.00 kHz");
+    }
+
+    #[test]
+    fn engineering_without_a_value() {
+        assert_eq!(engineering(None, "V", 3), "---- V");
+        assert_eq!(engineering(Some(f64::NAN), "V", 3), "---- V");
+        assert_eq!(engineering(Some(f64::INFINITY), "V", 3), "---- V");
+    }
+
+    #[test]
+    fn prefix_exponent_steps_by_three() {
+        assert_eq!(prefix_exponent(0), 0);
+        assert_eq!(prefix_exponent(2), 0);
+        assert_eq!(prefix_exponent(3), 3);
+        assert_eq!(prefix_exponent(-1), -3);
+        assert_eq!(prefix_exponent(-3), -3);
+        assert_eq!(prefix_exponent(-4), -6);
+        // nothing beyond pico or giga
+        assert_eq!(prefix_exponent(-20), -12);
+        assert_eq!(prefix_exponent(15), 9);
+    }
+
+    #[test]
+    fn with_prefix_scales_the_value() {
+        assert_eq!(with_prefix(1234.5, 3, 3, "Hz"), "1.234 kHz");
+        assert_eq!(with_prefix(2.5e-6, -6, 1, "s"), "2.5 µs");
+        assert_eq!(with_prefix(1.5, 0, 2, "V"), "1.50 V");
+        assert_eq!(with_prefix(3e12, 12, 0, "Hz"), "3000 GHz");
+    }
+
+    #[test]
+    fn items_by_scpi_name_or_label() {
+        assert_eq!(Item::from_name("VPP"), Some(Item::Vpp));
+        assert_eq!(Item::from_name("pdut"), Some(Item::Duty));
+        assert_eq!(Item::from_name("Duty"), Some(Item::Duty));
+        assert_eq!(Item::from_name("freq"), Some(Item::Frequency));
+        assert_eq!(Item::from_name("RTIM"), Some(Item::RiseTime));
+        assert_eq!(Item::from_name("rise"), Some(Item::RiseTime));
+        assert_eq!(Item::from_name("volts"), None);
+        for item in Item::ALL.iter() {
+            assert_eq!(Item::from_name(item.scpi()), Some(*item));
+            assert_eq!(Item::from_name(item.label()), Some(*item));
+        }
+    }
+
+    #[test]
+    fn duty_is_a_percentage() {
+        assert_eq!(Item::Duty.format(Some(0.25), 3), "25.0 %");
+        assert_eq!(Item::Duty.format(Some(0.5), 4), "50.00 %");
+        assert_eq!(Item::Duty.format(Some(0.5), 1), "50 %");
+        assert_eq!(Item::Duty.format(None, 3), "---- %");
+    }
+
+    fn meter() -> Meter {
+        Meter {
+            channels: vec![1, 2],
+            items: vec![Item::Vpp, Item::Duty],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn lines_have_one_channel_each() {
+        let measurements = [
+            Measurement {
+                channel: 1,
+                item: Item::Vpp,
+                value: Some(2.0),
+            },
+            Measurement {
+                channel: 1,
+                item: Item::Duty,
+                value: Some(0.5),
+            },
+            Measurement {
+                channel: 2,
+                item: Item::Vpp,
+                value: None,
+            },
+        ];
+        assert_eq!(
+            meter().lines(&measurements),
+            vec!["CH1  Vpp 2.00 V  Duty 50.0 %", "CH2  Vpp ---- V"]
+        );
+    }
+
+    #[test]
+    fn unmeasurable_values_show_dashes() {
+        // the mock answers 9.9e37 for anything but voltages
+        let mock = MockScope::start().unwrap();
+        let meter = Meter {
+            channels: vec![1],
+            items: vec![Item::Frequency],
+            ..Default::default()
+        };
+        let measurements = smol::block_on(async {
+            let mut scope = ds1054z::Scope::connect(&mock.address()).await.unwrap();
+            meter.fetch(&mut scope).await.unwrap()
+        });
+
+        assert_eq!(measurements[0].value, None);
+        assert_eq!(meter.lines(&measurements), vec!["CH1  Freq ---- Hz"]);
+    }
+}
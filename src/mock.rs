@@ -0,0 +1,671 @@
+// A fake DS1054Z that speaks just enough SCPI over TCP to exercise the
+// connect / grab / timeout / reconnect paths without a scope on the bench.
+
+use crate::discovery::Browser;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub const IDN: &str = "RIGOL TECHNOLOGIES,DS1054Z,DS1ZA000000001,00.04.04.SP4";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Behavior {
+    // answer every query
+    Normal,
+    // accept queries, but never answer them
+    Stall,
+    // close the connection when a query arrives
+    Drop,
+    // send only the first n bytes of each answer, then close
+    Truncate(usize),
+    // close every new connection immediately
+    Refuse,
+}
+
+#[derive(Debug)]
+struct State {
+    behavior: Behavior,
+    screen: Vec<u8>,
+    // (scale, offset, samples) for each displayed channel
+    channels: [Option<(f64, f64, Vec<f64>)>; 4],
+    source: usize,
+    // what :TRIG:STAT? answers
+    trigger: String,
+    // what :TRIG:SWE? answers
+    sweep: String,
+    requests: Vec<String>,
+    connections: usize,
+}
+
+#[derive(Debug)]
+pub struct MockScope {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    running: Arc<AtomicBool>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl MockScope {
+    pub fn start() -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State {
+            behavior: Behavior::Normal,
+            screen: test_pattern(800, 480),
+            channels: [Some((1.0, 0.0, test_waveform(1200))), None, None, None],
+            source: 1,
+            trigger: String::from("AUTO"),
+            sweep: String::from("AUTO"),
+            requests: vec![],
+            connections: 0,
+        }));
+        let running = Arc::new(AtomicBool::new(true));
+
+        let thread = {
+            let state = state.clone();
+            let running = running.clone();
+            std::thread::spawn(move || Self::accept_thread(listener, state, running))
+        };
+
+        Ok(MockScope {
+            addr,
+            state,
+            running,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn address(&self) -> String {
+        self.addr.to_string()
+    }
+
+    pub fn set_behavior(&self, behavior: Behavior) {
+        self.state.lock().unwrap().behavior = behavior;
+    }
+
+    pub fn set_screen(&self, bmp: Vec<u8>) {
+        self.state.lock().unwrap().screen = bmp;
+    }
+
+    // show a channel's trace, or hide it with None
+    pub fn set_channel(&self, channel: usize, trace: Option<(f64, f64, Vec<f64>)>) {
+        self.state.lock().unwrap().channels[channel - 1] = trace;
+    }
+
+    // like TD, WAIT, RUN, AUTO or STOP, as the scope says them
+    pub fn set_trigger(&self, status: &str) {
+        self.state.lock().unwrap().trigger = status.to_owned();
+    }
+
+    // like AUTO, NORM or SING, as the scope says them
+    pub fn set_sweep(&self, sweep: &str) {
+        self.state.lock().unwrap().sweep = sweep.to_owned();
+    }
+
+    // every line received so far, across all connections
+    pub fn requests(&self) -> Vec<String> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    // number of connections accepted so far, including refused ones
+    pub fn connections(&self) -> usize {
+        self.state.lock().unwrap().connections
+    }
+
+    fn accept_thread(listener: TcpListener, state: Arc<Mutex<State>>, running: Arc<AtomicBool>) {
+        for stream in listener.incoming() {
+            if !running.load(Ordering::SeqCst) {
+                return;
+            }
+            let stream = match stream {
+                Ok(s) => s,
+                Err(_) => continue,
+            };
+
+            let behavior = {
+                let mut st = state.lock().unwrap();
+                st.connections += 1;
+                st.behavior
+            };
+            if behavior == Behavior::Refuse {
+                continue;
+            }
+
+            let state = state.clone();
+            let running = running.clone();
+            std::thread::spawn(move || {
+                // errors here just mean the client went away
+                let _ = Self::client_thread(stream, state, running);
+            });
+        }
+    }
+
+    fn client_thread(
+        stream: TcpStream,
+        state: Arc<Mutex<State>>,
+        running: Arc<AtomicBool>,
+    ) -> std::io::Result<()> {
+        let mut writer = stream.try_clone()?;
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            let cmd = line.trim().to_owned();
+            if cmd.is_empty() {
+                continue;
+            }
+
+            let (behavior, reply) = {
+                let mut st = state.lock().unwrap();
+                st.requests.push(cmd.clone());
+                (st.behavior, respond(&mut st, &cmd))
+            };
+
+            // commands get no reply, and neither do unknown queries
+            let reply = match reply {
+                Some(r) => r,
+                None => continue,
+            };
+
+            match behavior {
+                Behavior::Normal | Behavior::Refuse => writer.write_all(&reply)?,
+                Behavior::Stall => {
+                    while running.load(Ordering::SeqCst) {
+                        std::thread::sleep(Duration::from_millis(10));
+                    }
+                    return Ok(());
+                }
+                Behavior::Drop => return Ok(()),
+                Behavior::Truncate(n) => {
+                    writer.write_all(&reply[..n.min(reply.len())])?;
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+impl Drop for MockScope {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        // wake up the accept thread so it notices
+        let _ = TcpStream::connect(self.addr);
+        if let Some(thread) = self.thread.take() {
+            thread.join().expect("could not join thread");
+        }
+    }
+}
+
+// stands in for the portmapper on a networked scope, so discovery can be
+// pointed at it instead of broadcasting
+#[derive(Debug)]
+pub struct Responder {
+    addr: SocketAddr,
+    scope: SocketAddr,
+    running: Arc<AtomicBool>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl Responder {
+    pub fn start(scope: &MockScope) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind("127.0.0.1:0")?;
+        let addr = socket.local_addr()?;
+        let running = Arc::new(AtomicBool::new(true));
+
+        let thread = {
+            let running = running.clone();
+            std::thread::spawn(move || {
+                let mut buf = [0; 1500];
+                while running.load(Ordering::SeqCst) {
+                    let (n, from) = match socket.recv_from(&mut buf) {
+                        Ok(r) => r,
+                        Err(_) => continue,
+                    };
+                    // GETPORT calls are 14 words; answer with a made-up port
+                    if n == 56 {
+                        let mut reply = buf[..4].to_vec();
+                        for w in &[1u32, 0, 0, 0, 0, 1024] {
+                            reply.extend_from_slice(&w.to_be_bytes());
+                        }
+                        let _ = socket.send_to(&reply, from);
+                    }
+                }
+            })
+        };
+
+        Ok(Responder {
+            addr,
+            scope: scope.addr,
+            running,
+            thread: Some(thread),
+        })
+    }
+
+    // a browser that finds only the mock scope
+    pub fn browser(&self) -> Browser {
+        Browser {
+            // nothing answers mdns here
+            mdns: self.addr,
+            portmap: self.addr,
+            raw_port: self.scope.port(),
+            timeout: Duration::from_millis(200),
+        }
+    }
+}
+
+impl Drop for Responder {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        // wake up the thread so it notices
+        if let Ok(s) = UdpSocket::bind("127.0.0.1:0") {
+            let _ = s.send_to(&[], self.addr);
+        }
+        if let Some(thread) = self.thread.take() {
+            thread.join().expect("could not join thread");
+        }
+    }
+}
+
+fn respond(state: &mut State, cmd: &str) -> Option<Vec<u8>> {
+    let cmd = cmd.to_uppercase();
+    let mut parts = cmd.split_whitespace();
+    let header = parts.next()?;
+    let arg = parts.next().unwrap_or("");
+
+    // :CHANn:... commands, for n in 1 through 4
+    let channel = header
+        .strip_prefix(":CHAN")
+        .and_then(|h| h.get(..1))
+        .and_then(|n| n.parse::<usize>().ok())
+        .filter(|n| (1..=4).contains(n));
+    if let Some(n) = channel {
+        let trace = &state.channels[n - 1];
+        let reply = match &header[6..] {
+            ":DISP?" => if trace.is_some() { "1" } else { "0" }.to_owned(),
+            ":SCAL?" => format!("{:e}", trace.as_ref().map(|t| t.0).unwrap_or(1.0)),
+            ":OFFS?" => format!("{:e}", trace.as_ref().map(|t| t.1).unwrap_or(0.0)),
+            _ => return None,
+        };
+        return Some(format!("{}\n", reply).into_bytes());
+    }
+
+    match header {
+        "*IDN?" => Some(format!("{}\n", IDN).into_bytes()),
+        ":DISP:DATA?" | ":DISPLAY:DATA?" => Some(block(&state.screen)),
+        ":WAV:SOUR" | ":WAVEFORM:SOURCE" => {
+            if let Some(n) = arg.strip_prefix("CHAN").and_then(|n| n.parse().ok()) {
+                state.source = n;
+            }
+            None
+        }
+        ":WAV:DATA?" | ":WAVEFORM:DATA?" => {
+            let samples = state
+                .channels
+                .get(state.source.wrapping_sub(1))
+                .and_then(|t| t.as_ref())
+                .map(|t| t.2.as_slice())
+                .unwrap_or(&[]);
+            let text: Vec<String> = samples.iter().map(|v| format!("{:e}", v)).collect();
+            Some(block(text.join(",").as_bytes()))
+        }
+        ":TRIG:STAT?" | ":TRIGGER:STATUS?" => Some(format!("{}\n", state.trigger).into_bytes()),
+        ":TRIG:SWE?" | ":TRIGGER:SWEEP?" => Some(format!("{}\n", state.sweep).into_bytes()),
+        // waits for a trigger that only set_trigger can give it
+        ":SING" | ":SINGLE" => {
+            state.trigger = String::from("WAIT");
+            state.sweep = String::from("SING");
+            None
+        }
+        ":STOP" => {
+            state.trigger = String::from("STOP");
+            None
+        }
+        ":MEAS:ITEM?" | ":MEASURE:ITEM?" => {
+            let mut args = arg.split(',');
+            let item = args.next().unwrap_or("");
+            let samples = args
+                .next()
+                .and_then(|s| s.strip_prefix("CHAN"))
+                .and_then(|n| n.parse::<usize>().ok())
+                .and_then(|n| state.channels.get(n.wrapping_sub(1)))
+                .and_then(|t| t.as_ref())
+                .map(|t| t.2.as_slice())
+                .filter(|s| !s.is_empty());
+            Some(format!("{:e}\n", measure(item, samples)).into_bytes())
+        }
+        _ => None,
+    }
+}
+
+// the mock has no time base, so only the voltage items are answered. the
+// rest get 9.9e37, which is what the scope says when it can't measure.
+fn measure(item: &str, samples: Option<&[f64]>) -> f64 {
+    let samples = match samples {
+        Some(s) => s,
+        None => return 9.9e37,
+    };
+    match item {
+        "VPP" => {
+            let max = samples.iter().cloned().fold(f64::MIN, f64::max);
+            let min = samples.iter().cloned().fold(f64::MAX, f64::min);
+            max - min
+        }
+        "VRMS" => (samples.iter().map(|v| v * v).sum::<f64>() / samples.len() as f64).sqrt(),
+        _ => 9.9e37,
+    }
+}
+
+// wrap data in an IEEE 488.2 definite-length block, as the scope does
+pub fn block(data: &[u8]) -> Vec<u8> {
+    let mut out = format!("#9{:09}", data.len()).into_bytes();
+    out.extend_from_slice(data);
+    out.push(b'\n');
+    out
+}
+
+// build a 24-bit, bottom-up BMP file, like the ones the scope sends
+pub fn screen_bmp<F>(width: u32, height: u32, pixel: F) -> Vec<u8>
+where
+    F: Fn(u32, u32) -> [u8; 3],
+{
+    let stride = (3 * width + 3) & !3;
+    let image_size = stride * height;
+    let offset = 14 + 40;
+
+    let mut out = Vec::with_capacity((offset + image_size) as usize);
+    // BITMAPFILEHEADER
+    out.extend_from_slice(b"BM");
+    out.extend_from_slice(&(offset + image_size).to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&offset.to_le_bytes());
+    // BITMAPINFOHEADER
+    out.extend_from_slice(&40u32.to_le_bytes());
+    out.extend_from_slice(&(width as i32).to_le_bytes());
+    out.extend_from_slice(&(height as i32).to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&24u16.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&image_size.to_le_bytes());
+    out.extend_from_slice(&2835i32.to_le_bytes());
+    out.extend_from_slice(&2835i32.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+
+    for y in (0..height).rev() {
+        let row_start = out.len();
+        for x in 0..width {
+            let [r, g, b] = pixel(x, y);
+            out.extend_from_slice(&[b, g, r]);
+        }
+        out.resize(row_start + stride as usize, 0);
+    }
+    out
+}
+
+// a recognizable screen: colored bars over a horizontal gradient
+pub fn test_pattern(width: u32, height: u32) -> Vec<u8> {
+    const BARS: [[u8; 3]; 4] = [
+        [0xff, 0xff, 0x00],
+        [0x00, 0xff, 0xff],
+        [0xff, 0x00, 0xff],
+        [0x00, 0x80, 0xff],
+    ];
+    screen_bmp(width, height, |x, y| {
+        if y < height / 2 {
+            BARS[(4 * x / width.max(1)) as usize]
+        } else {
+            let v = (255 * x / width.max(1)) as u8;
+            [v, v, v]
+        }
+    })
+}
+
+// two cycles of a 2 Vpp sine, as a screenful of samples
+pub fn test_waveform(points: usize) -> Vec<f64> {
+    (0..points)
+        .map(|i| (4.0 * std::f64::consts::PI * i as f64 / points as f64).sin())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::{Engine, Event, Frame, Grabber, Recorder, Settings};
+    use crate::connection::{Backoff, State};
+    use crate::source::Screen;
+    use smol_timeout::TimeoutExt;
+    use std::time::Instant;
+
+    // the size of one screen grab over a fresh connection, or None on timeout
+    async fn grab(mock: &MockScope) -> Option<Result<(u32, u32), String>> {
+        let mut scope = match ds1054z::Scope::connect(&mock.address()).await {
+            Ok(s) => s,
+            Err(e) => return Some(Err(format!("{:?}", e))),
+        };
+        let bmp = scope
+            .grab_screen()
+            .timeout(Duration::from_millis(500))
+            .await?;
+        Some(
+            bmp.map(|b| (b.width(), b.height()))
+                .map_err(|e| format!("{:?}", e)),
+        )
+    }
+
+    #[test]
+    fn normal_sends_the_screen() {
+        let mock = MockScope::start().unwrap();
+        mock.set_screen(screen_bmp(5, 3, |x, y| [x as u8, y as u8, 0x80]));
+        assert_eq!(smol::block_on(grab(&mock)), Some(Ok((5, 3))));
+        assert!(mock.requests()[0].starts_with(":DISP:DATA?"));
+        assert_eq!(mock.connections(), 1);
+    }
+
+    #[test]
+    fn stall_never_answers() {
+        let mock = MockScope::start().unwrap();
+        mock.set_behavior(Behavior::Stall);
+        assert!(smol::block_on(grab(&mock)).is_none());
+        assert_eq!(mock.requests().len(), 1);
+    }
+
+    fn fails_then_reconnects(behavior: Behavior) {
+        let mock = MockScope::start().unwrap();
+        mock.set_behavior(behavior);
+        assert!(matches!(smol::block_on(grab(&mock)), Some(Err(_))));
+
+        mock.set_behavior(Behavior::Normal);
+        assert!(matches!(smol::block_on(grab(&mock)), Some(Ok(_))));
+        assert_eq!(mock.connections(), 2);
+    }
+
+    #[test]
+    fn drop_fails_the_grab() {
+        fails_then_reconnects(Behavior::Drop);
+    }
+
+    #[test]
+    fn truncate_fails_the_grab() {
+        fails_then_reconnects(Behavior::Truncate(100));
+    }
+
+    #[test]
+    fn refuse_closes_before_any_request() {
+        let mock = MockScope::start().unwrap();
+        mock.set_behavior(Behavior::Refuse);
+        assert!(matches!(smol::block_on(grab(&mock)), Some(Err(_))));
+        assert!(mock.requests().is_empty());
+    }
+
+    #[test]
+    fn screens_that_are_not_24_bit_bmps_fail_the_grab() {
+        let mock = MockScope::start().unwrap();
+        let mut bmp = screen_bmp(5, 3, |_, _| [0xff, 0, 0]);
+        // say 32 bits per pixel, which the scope never sends
+        bmp[28] = 32;
+        mock.set_screen(bmp);
+        let result = smol::block_on(async {
+            let mut scope = ds1054z::Scope::connect(&mock.address()).await.unwrap();
+            let mut frame = Frame::new(5, 3);
+            Screen::default().grab(&mut scope, &mut frame).await
+        });
+        assert!(matches!(result, Err(crate::scpi::Error::Parse(_))));
+    }
+
+    fn engine(mock: &MockScope) -> Engine<Recorder, Screen> {
+        engine_giving_up_after(mock, 0)
+    }
+
+    // 0 to never give up
+    fn engine_giving_up_after(mock: &MockScope, max_attempts: u32) -> Engine<Recorder, Screen> {
+        let mut engine = Engine::new(Recorder::new(), Screen::default());
+        let settings = Settings {
+            address: mock.address(),
+            backoff: Backoff {
+                initial: Duration::from_millis(20),
+                max: Duration::from_secs(1),
+                jitter: 0.0,
+                max_attempts,
+                ..Default::default()
+            },
+            connect_timeout: Duration::from_millis(500),
+            ..Default::default()
+        };
+        engine.update(settings, Screen::default());
+        engine
+    }
+
+    // step the engine until `done`, or fail after a few seconds
+    fn step_until<F>(engine: &mut Engine<Recorder, Screen>, mut done: F)
+    where
+        F: FnMut(&Engine<Recorder, Screen>) -> bool,
+    {
+        let give_up = Instant::now() + Duration::from_secs(5);
+        smol::block_on(async {
+            while !done(engine) {
+                assert!(Instant::now() < give_up, "gave up in {}", engine.state());
+                engine.step().await;
+                smol::Timer::after(Duration::from_millis(5)).await;
+            }
+        });
+    }
+
+    fn disconnects(engine: &Engine<Recorder, Screen>) -> usize {
+        let events = engine.sink().events();
+        events
+            .iter()
+            .filter(|e| matches!(e, Event::Disconnected(_) | Event::ConnectFailed(_)))
+            .count()
+    }
+
+    #[test]
+    fn normal_grabs_the_screen() {
+        let mock = MockScope::start().unwrap();
+        mock.set_screen(screen_bmp(5, 3, |x, y| [x as u8, y as u8, 0x80]));
+        let mut engine = engine(&mock);
+        step_until(&mut engine, |e| !e.sink().frames().is_empty());
+
+        assert_eq!(engine.state(), State::Connected);
+        assert_eq!(
+            engine.sink().events(),
+            vec![Event::Connected(mock.address())]
+        );
+        let frame = &engine.sink().frames()[0];
+        assert_eq!((frame.width, frame.height), (5, 3));
+        // top-down RGBA, even though the bitmap is bottom-up BGR
+        assert_eq!(frame.data[..4], [0, 0, 0x80, 0xff]);
+        let last = 4 * (5 * 3 - 1);
+        assert_eq!(frame.data[last..], [4, 2, 0x80, 0xff]);
+        assert!(mock.requests().iter().any(|r| r.starts_with(":DISP:DATA?")));
+        assert_eq!(mock.connections(), 1);
+    }
+
+    #[test]
+    fn stall_times_out() {
+        let mock = MockScope::start().unwrap();
+        mock.set_behavior(Behavior::Stall);
+        let mut engine = engine(&mock);
+        let started = Instant::now();
+        step_until(&mut engine, |e| disconnects(e) > 0);
+
+        assert!(started.elapsed() >= Duration::from_millis(500));
+        assert_eq!(engine.state(), State::Backoff);
+        assert!(engine.sink().frames().is_empty());
+        let status = engine.status_handle().lock().unwrap().clone();
+        assert_eq!(status.last_error.as_deref(), Some("timed out"));
+    }
+
+    fn reconnects_after(behavior: Behavior) {
+        let mock = MockScope::start().unwrap();
+        mock.set_behavior(behavior);
+        let mut engine = engine(&mock);
+        step_until(&mut engine, |e| disconnects(e) > 0);
+        assert_eq!(engine.state(), State::Backoff);
+        assert!(engine.sink().frames().is_empty());
+
+        mock.set_behavior(Behavior::Normal);
+        step_until(&mut engine, |e| !e.sink().frames().is_empty());
+        assert_eq!(engine.state(), State::Connected);
+        assert_eq!(
+            engine.sink().events().last(),
+            Some(&Event::Connected(mock.address()))
+        );
+        assert!(mock.connections() >= 2);
+    }
+
+    #[test]
+    fn drop_reconnects() {
+        reconnects_after(Behavior::Drop);
+    }
+
+    #[test]
+    fn truncate_reconnects() {
+        reconnects_after(Behavior::Truncate(100));
+    }
+
+    #[test]
+    fn refuse_backs_off() {
+        let mock = MockScope::start().unwrap();
+        mock.set_behavior(Behavior::Refuse);
+        let mut engine = engine(&mock);
+
+        // the wait before each retry, right after each failure
+        let mut delays = vec![];
+        let status = engine.status_handle();
+        step_until(&mut engine, |e| {
+            if disconnects(e) > delays.len() {
+                delays.push(status.lock().unwrap().retry_in.unwrap());
+            }
+            delays.len() == 4
+        });
+
+        assert!(engine.sink().frames().is_empty());
+        for pair in delays.windows(2) {
+            assert!(pair[1] > pair[0] * 3 / 2, "{:?}", delays);
+        }
+        assert!(mock.connections() <= 4, "{}", mock.connections());
+    }
+
+    #[test]
+    fn failing_grabs_give_up() {
+        let mock = MockScope::start().unwrap();
+        mock.set_behavior(Behavior::Drop);
+        let mut engine = engine_giving_up_after(&mock, 3);
+        step_until(&mut engine, |e| e.state() == State::Failed);
+
+        assert_eq!(disconnects(&engine), 3);
+        assert_eq!(mock.connections(), 3);
+        // and stays given up
+        for _ in 0..5 {
+            smol::block_on(engine.step());
+        }
+        assert_eq!(engine.state(), State::Failed);
+        assert_eq!(mock.connections(), 3);
+    }
+}
@@ -9,6 +9,9 @@ impl bobs::ModuleInfo for DS1054ZModule {
     fn load(r: &mut bobs::Registrar) -> Option<Self> {
         use bobs::SourceImpl;
         r.register(crate::source::ScopeSource::info());
+        r.register(crate::gpu::GpuScopeSource::info());
+        r.register(crate::waveform::WaveformSource::info());
+        r.register(crate::measure::MeasureSource::info());
         Some(DS1054ZModule)
     }
 }
@@ -0,0 +1,52 @@
+use crate::capture::Frame;
+use crate::connection::Status;
+use crate::font;
+use crate::render::Color;
+
+// how much of the last good frame shows through
+const DIM: f64 = 0.3;
+
+const HEADLINE: Color = Color::WHITE;
+const REASON: Color = Color::rgb(0xff, 0x80, 0x80);
+const BACKDROP: Color = Color::rgba(0x00, 0x00, 0x00, 0xa0);
+
+// draw a placeholder saying why there's no picture
+pub fn render(frame: &mut Frame, last: Option<&Frame>, status: &Status) {
+    match last {
+        Some(last) => {
+            frame.clone_from(last);
+            frame.dim(DIM);
+        }
+        None => frame.fill(Color::BLACK),
+    }
+
+    let mut lines = vec![(status.headline(), HEADLINE)];
+    if let Some(reason) = status.reason() {
+        lines.push((reason, REASON));
+    }
+
+    // as big as fits, up to a sixth of the frame height per line
+    let widest = lines
+        .iter()
+        .map(|(s, _)| font::text_width(s))
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let scale = (frame.height / (6 * font::HEIGHT))
+        .min(9 * frame.width / (10 * widest))
+        .max(1);
+
+    let line = ((font::HEIGHT + 4) * scale) as i32;
+    let total = line * lines.len() as i32;
+    let top = (frame.height as i32 - total) / 2;
+    frame.rect(
+        0,
+        top - line / 2,
+        frame.width as i32,
+        total + line,
+        BACKDROP,
+    );
+    for (i, (text, color)) in lines.iter().enumerate() {
+        frame.text_centered(top + i as i32 * line, scale, *color, text);
+    }
+}
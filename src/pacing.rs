@@ -0,0 +1,114 @@
+use std::time::{Duration, Instant};
+
+// fraction of the grab latency left idle between grabs in adaptive mode,
+// so the scope's UI has time to breathe
+const HEADROOM: f64 = 0.5;
+
+// weight of each new measurement in the running averages
+const SMOOTHING: f64 = 0.2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pacing {
+    // the rate to aim for, and never exceed
+    pub fps: f64,
+    // slow down to match how fast the scope actually answers
+    pub adaptive: bool,
+}
+
+impl Default for Pacing {
+    fn default() -> Self {
+        Pacing {
+            fps: 10.0,
+            adaptive: false,
+        }
+    }
+}
+
+impl Pacing {
+    pub fn period(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.fps.max(0.1))
+    }
+}
+
+// what the pacer has measured, shared with whoever wants to display it
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Stats {
+    pub fps: Option<f64>,
+    pub latency: Option<Duration>,
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.fps {
+            Some(fps) => write!(f, "{:.1} fps", fps)?,
+            None => write!(f, "no frames")?,
+        }
+        if let Some(l) = self.latency {
+            write!(f, ", grab takes {} ms", l.as_millis())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Pacer {
+    pacing: Pacing,
+    latency: Option<f64>,
+    interval: Option<f64>,
+    last_frame: Option<Instant>,
+}
+
+impl Pacer {
+    pub fn new(pacing: Pacing) -> Self {
+        Pacer {
+            pacing,
+            ..Default::default()
+        }
+    }
+
+    pub fn set_pacing(&mut self, pacing: Pacing) {
+        self.pacing = pacing;
+    }
+
+    // a grab started at `started` just finished successfully
+    pub fn grabbed(&mut self, started: Instant) {
+        let now = Instant::now();
+        let latency = (now - started).as_secs_f64();
+        self.latency = Some(smooth(self.latency, latency));
+        if let Some(last) = self.last_frame {
+            self.interval = Some(smooth(self.interval, (now - last).as_secs_f64()));
+        }
+        self.last_frame = Some(now);
+    }
+
+    // no frames are coming for a while, so forget the frame rate
+    pub fn stalled(&mut self) {
+        self.interval = None;
+        self.last_frame = None;
+    }
+
+    // when to start the next cycle, given when this one started
+    pub fn next(&self, started: Instant) -> Instant {
+        let mut period = self.pacing.period();
+        if self.pacing.adaptive {
+            if let Some(latency) = self.latency {
+                period = period.max(Duration::from_secs_f64(latency * (1.0 + HEADROOM)));
+            }
+        }
+        started + period
+    }
+
+    pub fn stats(&self) -> Stats {
+        Stats {
+            fps: self.interval.filter(|i| *i > 0.0).map(|i| 1.0 / i),
+            latency: self.latency.map(Duration::from_secs_f64),
+        }
+    }
+}
+
+fn smooth(avg: Option<f64>, x: f64) -> f64 {
+    match avg {
+        Some(avg) => avg + SMOOTHING * (x - avg),
+        None => x,
+    }
+}
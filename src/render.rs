@@ -0,0 +1,170 @@
+use crate::capture::Frame;
+use crate::font;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const BLACK: Color = Color::rgb(0x00, 0x00, 0x00);
+    pub const WHITE: Color = Color::rgb(0xff, 0xff, 0xff);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b, a: 0xff }
+    }
+
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Color { r, g, b, a }
+    }
+
+    // OBS stores colors as 0xAABBGGRR
+    pub fn from_obs(c: i64) -> Self {
+        let c = c as u32;
+        Color {
+            r: c as u8,
+            g: (c >> 8) as u8,
+            b: (c >> 16) as u8,
+            a: (c >> 24) as u8,
+        }
+    }
+
+    pub fn to_obs(self) -> i64 {
+        (self.r as u32 | (self.g as u32) << 8 | (self.b as u32) << 16 | (self.a as u32) << 24)
+            as i64
+    }
+
+    pub fn with_alpha(self, a: u8) -> Self {
+        Color { a, ..self }
+    }
+}
+
+impl Frame {
+    pub fn fill(&mut self, c: Color) {
+        for px in self.data.chunks_exact_mut(4) {
+            px.copy_from_slice(&[c.r, c.g, c.b, 0xff]);
+        }
+    }
+
+    // like fill, but keeping the color's alpha, for frames drawn over others
+    pub fn fill_transparent(&mut self, c: Color) {
+        for px in self.data.chunks_exact_mut(4) {
+            px.copy_from_slice(&[c.r, c.g, c.b, c.a]);
+        }
+    }
+
+    // alpha-blend a single pixel, ignoring anything off-frame
+    pub fn blend(&mut self, x: i32, y: i32, c: Color) {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return;
+        }
+        let i = 4 * (y as usize * self.width as usize + x as usize);
+        let px = &mut self.data[i..i + 4];
+        let a = c.a as u32;
+        for (dst, src) in px.iter_mut().zip(&[c.r, c.g, c.b]) {
+            *dst = ((*src as u32 * a + *dst as u32 * (255 - a)) / 255) as u8;
+        }
+        // opaque frames stay opaque
+        px[3] = (a + px[3] as u32 * (255 - a) / 255) as u8;
+    }
+
+    pub fn rect(&mut self, x: i32, y: i32, w: i32, h: i32, c: Color) {
+        for py in y.max(0)..(y + h).min(self.height as i32) {
+            for px in x.max(0)..(x + w).min(self.width as i32) {
+                self.blend(px, py, c);
+            }
+        }
+    }
+
+    // a line `width` pixels thick, drawn by stamping squares along it
+    pub fn line(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, width: u32, c: Color) {
+        let steps = (x1 - x0).abs().max((y1 - y0).abs()).ceil().max(1.0) as u32;
+        let w = width.max(1) as i32;
+        let half = (w - 1) / 2;
+        for i in 0..=steps {
+            let t = i as f64 / steps as f64;
+            let x = (x0 + t * (x1 - x0)).round() as i32;
+            let y = (y0 + t * (y1 - y0)).round() as i32;
+            // stamping overlaps itself, so don't blend translucent lines
+            if c.a == 0xff {
+                self.rect(x - half, y - half, w, w, c);
+            } else {
+                self.blend(x, y, c);
+            }
+        }
+    }
+
+    pub fn hline(&mut self, y: i32, c: Color, dotted: bool) {
+        for x in 0..self.width as i32 {
+            if !dotted || x % 4 == 0 {
+                self.blend(x, y, c);
+            }
+        }
+    }
+
+    pub fn vline(&mut self, x: i32, c: Color, dotted: bool) {
+        for y in 0..self.height as i32 {
+            if !dotted || y % 4 == 0 {
+                self.blend(x, y, c);
+            }
+        }
+    }
+
+    // scale every color channel, leaving alpha alone
+    pub fn dim(&mut self, factor: f64) {
+        let f = (factor.clamp(0.0, 1.0) * 256.0) as u32;
+        for px in self.data.chunks_exact_mut(4) {
+            for v in &mut px[..3] {
+                *v = ((*v as u32 * f) >> 8) as u8;
+            }
+        }
+    }
+
+    // draw text with its top-left corner at (x, y), each font pixel scale x scale
+    pub fn text(&mut self, x: i32, y: i32, scale: u32, c: Color, s: &str) {
+        let scale = scale.max(1) as i32;
+        let advance = (font::WIDTH + font::SPACING) as i32 * scale;
+        for (i, ch) in font::normalize(s).chars().enumerate() {
+            let gx = x + i as i32 * advance;
+            for (col, bits) in font::glyph(ch).iter().enumerate() {
+                for row in 0..font::HEIGHT as i32 {
+                    if bits & (1 << row) != 0 {
+                        let px = gx + col as i32 * scale;
+                        self.rect(px, y + row * scale, scale, scale, c);
+                    }
+                }
+            }
+        }
+    }
+
+    // like text, but centered horizontally on the frame
+    pub fn text_centered(&mut self, y: i32, scale: u32, c: Color, s: &str) {
+        let w = (font::text_width(s) * scale.max(1)) as i32;
+        self.text((self.width as i32 - w) / 2, y, scale, c, s);
+    }
+
+    // keep only the given region, clipped to the frame
+    pub fn crop(&mut self, x: u32, y: u32, width: u32, height: u32) {
+        let x = x.min(self.width);
+        let y = y.min(self.height);
+        let width = width.min(self.width - x);
+        let height = height.min(self.height - y);
+        if (x, y, width, height) == (0, 0, self.width, self.height) {
+            return;
+        }
+
+        let src_stride = self.linesize() as usize;
+        let dst_stride = 4 * width as usize;
+        for row in 0..height as usize {
+            let src = (y as usize + row) * src_stride + 4 * x as usize;
+            self.data
+                .copy_within(src..src + dst_stride, row * dst_stride);
+        }
+        self.width = width;
+        self.height = height;
+        self.data.truncate(dst_stride * height as usize);
+    }
+}
@@ -0,0 +1,81 @@
+use smol_timeout::TimeoutExt;
+use std::time::Duration;
+
+// how long to wait for the scope to answer a single command
+const TIMEOUT: Duration = Duration::from_millis(1000);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    Timeout,
+    Scope(String),
+    Parse(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::Timeout => write!(f, "timed out"),
+            Error::Scope(e) => write!(f, "{}", e),
+            Error::Parse(e) => write!(f, "could not parse response: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub async fn send(scope: &mut ds1054z::Scope, cmd: &str) -> Result<(), Error> {
+    match scope.send(cmd).timeout(TIMEOUT).await {
+        Some(Ok(())) => Ok(()),
+        Some(Err(e)) => Err(Error::Scope(format!("{:?}", e))),
+        None => Err(Error::Timeout),
+    }
+}
+
+pub async fn query(scope: &mut ds1054z::Scope, cmd: &str) -> Result<String, Error> {
+    match scope.query(cmd).timeout(TIMEOUT).await {
+        Some(Ok(r)) => Ok(r.trim().to_owned()),
+        Some(Err(e)) => Err(Error::Scope(format!("{:?}", e))),
+        None => Err(Error::Timeout),
+    }
+}
+
+pub async fn query_f64(scope: &mut ds1054z::Scope, cmd: &str) -> Result<f64, Error> {
+    let r = query(scope, cmd).await?;
+    r.parse().map_err(|_| Error::Parse(r))
+}
+
+pub async fn query_bool(scope: &mut ds1054z::Scope, cmd: &str) -> Result<bool, Error> {
+    let r = query(scope, cmd).await?;
+    match r.as_str() {
+        "1" | "ON" => Ok(true),
+        "0" | "OFF" => Ok(false),
+        _ => Err(Error::Parse(r)),
+    }
+}
+
+// strip an IEEE 488.2 definite-length block header, like "#9000001200"
+pub fn strip_block(data: &str) -> Result<&str, Error> {
+    if !data.starts_with('#') {
+        return Ok(data);
+    }
+    let digits = data
+        .get(1..2)
+        .and_then(|d| d.parse::<usize>().ok())
+        .ok_or_else(|| Error::Parse(data.chars().take(11).collect()))?;
+    let len = data
+        .get(2..2 + digits)
+        .and_then(|d| d.parse::<usize>().ok())
+        .ok_or_else(|| Error::Parse(data.chars().take(11).collect()))?;
+    let body = &data[2 + digits..];
+    Ok(body.get(..len).unwrap_or(body))
+}
+
+// parse the result of :WAV:DATA? in ASCii format into volts
+pub fn parse_ascii_waveform(data: &str) -> Result<Vec<f64>, Error> {
+    strip_block(data)?
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(|v| v.parse().map_err(|_| Error::Parse(v.to_owned())))
+        .collect()
+}
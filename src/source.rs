@@ -1,181 +1,723 @@
+use crate::bitmap::Packed;
+use crate::capture::{
+    Command, Disconnect, Frame, FrameSink, Grabber, Remote, Settings, Trigger, Worker,
+};
+use crate::connection::Backoff;
+use crate::discovery;
+use crate::pacing::Pacing;
+use crate::scpi;
+use crate::tile::{Compositor, Layout, TileSink};
+use bobs::Settings as _;
 use smol_timeout::TimeoutExt;
-use std::sync::mpsc;
-use std::time::{Duration, Instant};
+use std::future::Future;
+use std::pin::Pin;
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// where things are on the 800x480 screen, as (x, y, width, height)
+const GRATICULE: (u32, u32, u32, u32) = (59, 46, 601, 401);
+const GRATICULE_CHANNELS: (u32, u32, u32, u32) = (0, 46, 670, 434);
 
 #[derive(Debug)]
 struct ThreadSafePtr<T>(*mut T);
 unsafe impl<T> Send for ThreadSafePtr<T> {}
 
 #[derive(Debug)]
-pub struct ScopeSource {
-    channel: mpsc::Sender<Message>,
-    thread: Option<std::thread::JoinHandle<()>>,
+pub struct ScopeSource<S = ObsSink> {
+    // unregistered when dropped
+    _hotkeys: Vec<bobs::Hotkey>,
+    // one worker per scope, all feeding the same compositor
+    workers: Vec<Worker<Screen>>,
+    remotes: Remotes<Screen>,
+    compositor: Arc<Mutex<Compositor<S>>>,
+    serial: String,
+    visibility: Visibility,
 }
 
-#[derive(Debug, Default)]
-struct Settings {
-    address: String,
-    blank: bool,
+// grabs the scope's own screen bitmap
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Screen {
+    pub crop: Crop,
 }
 
-#[derive(Debug)]
-enum Message {
-    End,
-    Update(Settings),
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Crop {
+    Full,
+    Graticule,
+    GraticuleChannels,
+    Custom {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+}
+
+// by hand, since #[default] on a variant needs a newer compiler
+#[allow(clippy::derivable_impls)]
+impl Default for Crop {
+    fn default() -> Self {
+        Crop::Full
+    }
 }
 
 impl bobs::SourceImpl for ScopeSource {
     const ID: &'static str = "ds1054z";
     const NAME: &'static str = "Rigol DS1054Z";
     const ICON_TYPE: bobs::IconType = bobs::IconType::WindowCapture;
+    const VERSION: i64 = SETTINGS_VERSION;
 
     fn output_flags() -> bobs::SourceFlags {
         bobs::SourceFlags::ASYNC_VIDEO
     }
 
+    fn callbacks() -> bobs::Callbacks {
+        bobs::Callbacks::ACTIVATE | bobs::Callbacks::SHOW
+    }
+
     fn create(settings: &bobs::Data, source: *mut obs_sys::obs_source_t) -> Self {
-        let (tx, rx) = std::sync::mpsc::channel();
-        let source = ThreadSafePtr(source);
-        let thread = std::thread::spawn(move || smol::block_on(Self::video_thread(rx, source.0)));
+        ScopeSource::new(settings, source, ObsSink::new(source))
+    }
+
+    fn get_properties(&mut self) -> Box<bobs::Properties> {
+        self.properties()
+    }
+
+    fn get_defaults(settings: &mut bobs::Data) {
+        set_defaults(settings);
+        View::defaults(settings);
+    }
+
+    fn migrate(settings: &mut bobs::Data, from: i64) {
+        migrate_settings(settings, from);
+    }
+
+    fn update(&mut self, settings: &bobs::Data) {
+        self.configure(settings);
+    }
+
+    fn activate(&mut self) {
+        self.set_active(true);
+    }
+
+    fn deactivate(&mut self) {
+        self.set_active(false);
+    }
+
+    fn show(&mut self) {
+        self.set_showing(true);
+    }
+
+    fn hide(&mut self) {
+        self.set_showing(false);
+    }
+}
+
+// the parts of the screen source that don't care where frames go
+impl<S> ScopeSource<S>
+where
+    S: FrameSink + 'static,
+{
+    pub fn new(settings: &bobs::Data, source: *mut obs_sys::obs_source_t, sink: S) -> Self {
+        let compositor = Compositor::new(sink, Layout::Horizontal, 0);
+        let remotes = Remotes::default();
         let mut src = ScopeSource {
-            thread: Some(thread),
-            channel: tx,
+            _hotkeys: register_hotkeys(source, &remotes),
+            workers: vec![],
+            remotes,
+            compositor: Arc::new(Mutex::new(compositor)),
+            serial: String::new(),
+            visibility: Visibility::default(),
         };
-         src.update(settings);
+        src.configure(settings);
         src
     }
 
-    fn get_properties(&mut self) -> Box<bobs::Properties> {
+    pub fn properties(&self) -> Box<bobs::Properties> {
         let mut props = bobs::Properties::create();
-        props.add_text("address", "Oscilloscope address", bobs::TextType::Default);
-        props.add_bool("blank", "Blank when disconnected.");
+        let status: Vec<String> = self.workers.iter().map(status_line).collect();
+        add_properties(
+            &mut props,
+            &self.serial,
+            "host:port of the scope's raw SCPI socket, usually port 5555. \
+             List several, separated by commas, to show them all in one source.",
+            &status.join(" | "),
+        );
+
+        View::properties(&mut props);
+        props.set_modified_callback("address", |props, settings| {
+            show(
+                props,
+                &["layout"],
+                settings.get_string("address").contains(','),
+            );
+            true
+        });
+        props.set_modified_callback("crop", |props, settings| {
+            let custom = settings.get_string("crop") == "custom";
+            show(
+                props,
+                &["crop_x", "crop_y", "crop_width", "crop_height"],
+                custom,
+            );
+            true
+        });
         props
     }
 
-    fn get_defaults(settings: &mut bobs::Data) {
-        settings.set_default_string("address", "ds1054z.local:555");
-        settings.set_default_bool("blank", true);
+    pub fn configure(&mut self, settings: &bobs::Data) {
+        let view = View::from_data(settings);
+        let crop = view.crop();
+        let layout = match view.layout.as_str() {
+            "vertical" => Layout::Vertical,
+            "grid" => Layout::Grid,
+            _ => Layout::Horizontal,
+        };
+
+        // several scopes can share a source, separated by commas
+        let shared = read_settings(settings);
+        let mut addresses: Vec<&str> = shared
+            .address
+            .split(',')
+            .map(str::trim)
+            .filter(|a| !a.is_empty())
+            .collect();
+        if addresses.is_empty() {
+            // still need one worker, to report there's no address
+            addresses.push("");
+        }
+
+        self.compositor
+            .lock()
+            .unwrap()
+            .set_layout(layout, addresses.len());
+        self.workers.truncate(addresses.len());
+        while self.workers.len() < addresses.len() {
+            let sink = TileSink::new(self.workers.len(), self.compositor.clone());
+            self.workers.push(Worker::spawn(sink, Screen::default()));
+        }
+        *self.remotes.lock().unwrap() = self.workers.iter().map(Worker::remote).collect();
+
+        // a discovered scope takes the place of the first address
+        self.serial = shared.serial.clone();
+        for (i, (worker, address)) in self.workers.iter().zip(addresses).enumerate() {
+            let settings = Settings {
+                address: address.to_owned(),
+                serial: if i == 0 {
+                    shared.serial.clone()
+                } else {
+                    String::new()
+                },
+                ..shared.clone()
+            };
+            worker.update(settings, Screen { crop });
+        }
+        self.visibility.configure(settings);
+        self.pause(self.visibility.paused());
     }
 
-    fn update(&mut self, settings: &bobs::Data) {
-        let settings = Settings {
-            address: settings.get_string("address").to_owned(),
-            blank: settings.get_bool("blank"),
-        };
-        self.channel
-            .send(Message::Update(settings))
-            .expect("could not update settings");
+    pub fn set_active(&mut self, active: bool) {
+        let paused = self.visibility.set_active(active);
+        self.pause(paused);
+    }
+
+    pub fn set_showing(&mut self, showing: bool) {
+        let paused = self.visibility.set_showing(showing);
+        self.pause(paused);
+    }
+
+    // tell every worker whether anyone is watching
+    fn pause(&self, paused: bool) {
+        for worker in &self.workers {
+            worker.set_paused(paused);
+        }
     }
 }
 
-impl ScopeSource {
-    async fn video_thread(channel: mpsc::Receiver<Message>, source: *mut obs_sys::obs_source_t) {
-        // set up state
-        let mut settings: Settings = Default::default();
-        let mut scope = None;
+bobs::settings! {
+    // how the screen source shows each scope
+    #[derive(Debug, Clone)]
+    pub struct View {
+        pub layout: String = String::from("horizontal"), "Layout for multiple scopes", List(&[
+            ("Side by side", "horizontal"),
+            ("Stacked", "vertical"),
+            ("Grid", "grid"),
+        ]);
+        pub crop: String = String::from("full"), "Crop to", List(&[
+            ("Full screen", "full"),
+            ("Graticule", "graticule"),
+            ("Graticule and channel bar", "graticule_channels"),
+            ("Custom", "custom"),
+        ]);
+        pub crop_x: i64 = GRATICULE.0 as i64,
+            "Custom crop left", Int { min: 0, max: 800, step: 1 };
+        pub crop_y: i64 = GRATICULE.1 as i64,
+            "Custom crop top", Int { min: 0, max: 480, step: 1 };
+        pub crop_width: i64 = GRATICULE.2 as i64,
+            "Custom crop width", Int { min: 1, max: 800, step: 1 };
+        pub crop_height: i64 = GRATICULE.3 as i64,
+            "Custom crop height", Int { min: 1, max: 480, step: 1 };
+    }
+}
 
-        // set up frame
-        let mut frame = obs_sys::obs_source_frame {
-            format: obs_sys::video_format_VIDEO_FORMAT_RGBA,
-            width: 800,
-            height: 480,
-            ..Default::default()
+impl View {
+    fn crop(&self) -> Crop {
+        match self.crop.as_str() {
+            "graticule" => Crop::Graticule,
+            "graticule_channels" => Crop::GraticuleChannels,
+            "custom" => Crop::Custom {
+                x: self.crop_x as u32,
+                y: self.crop_y as u32,
+                width: self.crop_width as u32,
+                height: self.crop_height as u32,
+            },
+            _ => Crop::Full,
+        }
+    }
+}
+
+impl Crop {
+    // the region to keep, as (x, y, width, height), clipped to a frame of
+    // the given size. a region off the frame keeps all of it instead.
+    pub fn rect(&self, frame_width: u32, frame_height: u32) -> (u32, u32, u32, u32) {
+        let (x, y, width, height) = match *self {
+            Crop::Full => return (0, 0, frame_width, frame_height),
+            Crop::Graticule => GRATICULE,
+            Crop::GraticuleChannels => GRATICULE_CHANNELS,
+            Crop::Custom {
+                x,
+                y,
+                width,
+                height,
+            } => (x, y, width, height),
         };
-        frame.linesize[0] = 4 * frame.width;
-        let mut data: Vec<u8> = vec![0xff; (4 * frame.width * frame.height) as usize];
-        frame.data[0] = data.as_slice().as_ptr() as *mut u8;
-
-        loop {
-            // wait until this time at end of loop...
-            let loop_end = Instant::now() + Duration::from_millis(100);
-
-            // attempt to connect
-            if let None = scope {
-                if settings.address != "" {
-                    if let Some(Ok(s)) = ds1054z::Scope::connect(&settings.address)
-                        .timeout(Duration::from_millis(1000))
-                        .await
-                    {
-                        log::info!("connected to {}", settings.address);
-                        scope = Some(s);
-                    }
-                }
-            }
-
-            // grab a frame
-            if let Some(mut s) = scope.take() {
-                let bmpr = s.grab_screen().timeout(Duration::from_millis(2000)).await;
-                if let Some(Ok(bmp)) = bmpr {
-                    // expand frame, if needed
-                    let newsize = (4 * bmp.width() * bmp.height()) as usize;
-                    if data.len() < newsize {
-                        data.resize(newsize, 0xff);
-                    }
-                    frame.linesize[0] = 4 * bmp.width();
-                    frame.width = bmp.width();
-                    frame.height = bmp.height();
-
-                    // fill frame
-                    for (i, v) in bmp.data().iter().enumerate() {
-                        let dst = (i / 3) + i;
-                        data[dst] = *v;
-                    }
+        let x = x.min(frame_width);
+        let y = y.min(frame_height);
+        let width = width.min(frame_width - x);
+        let height = height.min(frame_height - y);
+        if width == 0 || height == 0 {
+            return (0, 0, frame_width, frame_height);
+        }
+        (x, y, width, height)
+    }
+}
 
-                    // present texture
-                    unsafe {
-                        obs_sys::obs_source_output_video(source, &frame);
-                    }
+impl Screen {
+    async fn grab_screen(
+        self,
+        scope: &mut ds1054z::Scope,
+        frame: &mut Frame,
+    ) -> Result<(), scpi::Error> {
+        let bmp = match scope
+            .grab_screen()
+            .timeout(Duration::from_millis(2000))
+            .await
+        {
+            Some(Ok(bmp)) => bmp,
+            Some(Err(e)) => return Err(scpi::Error::Scope(format!("{:?}", e))),
+            None => return Err(scpi::Error::Timeout),
+        };
 
-                    // re-use scope
-                    scope = Some(s);
-                } else {
-                    log::info!("disconnected from {}", settings.address);
-                }
-            } else if settings.blank {
-                // fill frame with black
-                for (i, v) in data.iter_mut().enumerate() {
-                    if i % 4 != 3 {
-                        *v = 0;
-                    }
-                }
+        // the scope sends a whole 24-bit BMP file, and its header says how
+        // the rows are laid out
+        let data = bmp.data();
+        let packed = Packed::from_bmp(data).ok_or_else(|| {
+            scpi::Error::Parse(format!("screen is not a 24-bit BMP ({} bytes)", data.len()))
+        })?;
+        if !frame.fill_packed(data, &packed) {
+            return Err(scpi::Error::Parse(format!(
+                "{}x{} screen in {} bytes",
+                packed.width,
+                packed.height,
+                data.len()
+            )));
+        }
 
-                // present texture
-                unsafe {
-                    obs_sys::obs_source_output_video(source, &frame);
-                }
-            }
-
-            // look for end-thread message
-            match channel.try_recv() {
-                Ok(Message::End) => return,
-                Ok(Message::Update(s)) => {
-                    if scope.is_some() {
-                        log::info!("disconnected from {}", settings.address);
+        let (x, y, width, height) = self.crop.rect(frame.width, frame.height);
+        frame.crop(x, y, width, height);
+        Ok(())
+    }
+}
+
+impl Grabber for Screen {
+    fn grab<'a>(
+        &'a mut self,
+        scope: &'a mut ds1054z::Scope,
+        frame: &'a mut Frame,
+    ) -> Pin<Box<dyn Future<Output = Result<(), scpi::Error>> + 'a>> {
+        Box::pin(self.grab_screen(scope, frame))
+    }
+}
+
+pub fn status_line<G>(worker: &Worker<G>) -> String
+where
+    G: Grabber + 'static,
+{
+    format!("{}, {}", worker.status(), worker.stats())
+}
+
+bobs::settings! {
+    // which scope to talk to
+    #[derive(Debug, Clone)]
+    pub struct Target {
+        // filled in from discovery
+        pub device: String = String::new(), "Oscilloscope", List(&[]);
+        pub address: String = String::from("ds1054z.local:555"),
+            "Oscilloscope address", Text(bobs::TextType::Default);
+    }
+}
+
+bobs::settings! {
+    #[derive(Debug, Clone)]
+    pub struct Common {
+        pub on_disconnect: String = String::from("overlay"), "When disconnected", List(&[
+            ("Show connection status", "overlay"),
+            ("Hold last frame", "hold"),
+            ("Hold last frame, then blank", "hold_then_blank"),
+            ("Fade to black", "fade"),
+            ("Blank", "blank"),
+        ]);
+        pub disconnect_seconds: i64 = 5,
+            "Hold / fade time (s)", Int { min: 0, max: 3600, step: 1 };
+        pub fps: i64 = 10, "Frame rate (fps)", IntSlider { min: 1, max: 15, step: 1 };
+        pub adaptive: bool = false, "Slow down to match scope latency", Bool;
+        pub connect_timeout: i64 = 1000,
+            "Connect timeout (ms)", Int { min: 100, max: 10000, step: 100 };
+        pub run_when: String = String::from("visible"), "Capture", List(&[
+            ("Only when live", "live"),
+            ("When visible, including preview", "visible"),
+        ]);
+        pub disconnect_paused: bool = false, "Disconnect while not capturing", Bool;
+        pub trigger: String = String::from("always"), "Grab", List(&[
+            ("Continuously", "always"),
+            ("Once per trigger, in SINGLE mode", "single"),
+        ]);
+        pub rearm: bool = true, "Press SINGLE again after each grab", Bool;
+    }
+}
+
+bobs::settings! {
+    #[derive(Debug, Clone)]
+    pub struct Retry {
+        pub retry_initial: i64 = Backoff::default().initial.as_millis() as i64,
+            "First retry after (ms)", Int { min: 10, max: 10000, step: 10 };
+        pub retry_max: i64 = Backoff::default().max.as_millis() as i64,
+            "Longest retry interval (ms)", Int { min: 100, max: 600000, step: 100 };
+        pub retry_jitter: i64 = (100.0 * Backoff::default().jitter) as i64,
+            "Retry jitter (%)", IntSlider { min: 0, max: 100, step: 1 };
+        pub retry_limit: i64 = Backoff::default().max_attempts as i64,
+            "Give up after attempts (0 = never)", Int { min: 0, max: 1000, step: 1 };
+    }
+}
+
+// what the address field is, for sources that show one scope
+pub const ADDRESS_HELP: &str = "host:port of the scope's raw SCPI socket, usually port 5555";
+
+// how long the search button waits for a browse to finish
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(2);
+
+// properties shared by every source that talks to a scope
+pub fn add_properties(
+    props: &mut bobs::Properties,
+    serial: &str,
+    address_help: &str,
+    status: &str,
+) {
+    Target::properties(props);
+    if let Some(mut device) = props.get("device") {
+        list_devices(&mut device, serial);
+    }
+    if let Some(mut address) = props.get("address") {
+        address.set_long_description(address_help);
+    }
+    let serial = serial.to_owned();
+    props.add_button("search", "Search for oscilloscopes", move |props, _| {
+        // the ui waits on this, but a browse is short, and listing before it
+        // finishes would show what was there last time
+        if !discovery::refresh_and_wait(SEARCH_TIMEOUT) {
+            log::info!("still searching, listing the oscilloscopes found so far");
+        }
+        if let Some(mut device) = props.get("device") {
+            list_devices(&mut device, &serial);
+        }
+        true
+    });
+
+    Common::properties(props);
+    props.set_modified_callback("on_disconnect", |props, settings| {
+        let timed = matches!(
+            settings.get_string("on_disconnect"),
+            "hold_then_blank" | "fade"
+        );
+        show(props, &["disconnect_seconds"], timed);
+        true
+    });
+    props.set_modified_callback("trigger", |props, settings| {
+        let single = settings.get_string("trigger") == "single";
+        show(props, &["rearm"], single);
+        true
+    });
+
+    let mut retry = bobs::Properties::create();
+    Retry::properties(&mut retry);
+    props.add_group("retry", "Reconnecting", bobs::GroupType::Normal, retry);
+    props
+        .add_text(
+            "status",
+            &format!("Status: {}", status),
+            bobs::TextType::Default,
+        )
+        .set_enabled(false);
+}
+
+// workers a source's hotkeys talk to, kept up to date as they come and go
+pub type Remotes<G> = Arc<Mutex<Vec<Remote<G>>>>;
+
+// a hotkey for each front-panel button, pressing it on every scope
+pub fn register_hotkeys<G>(
+    source: *mut obs_sys::obs_source_t,
+    remotes: &Remotes<G>,
+) -> Vec<bobs::Hotkey>
+where
+    G: Send + 'static,
+{
+    let source: Box<bobs::Source> = match NonNull::new(source) {
+        Some(s) => unsafe { bobs::prelude::ObsRawCounted::from_raw_unowned(s) },
+        None => return vec![],
+    };
+    Command::ALL
+        .iter()
+        .filter_map(|&command| {
+            let (name, description) = match command {
+                Command::Run => ("ds1054z.run", "Oscilloscope: Run"),
+                Command::Stop => ("ds1054z.stop", "Oscilloscope: Stop"),
+                Command::Single => ("ds1054z.single", "Oscilloscope: Single trigger"),
+                Command::Clear => ("ds1054z.clear", "Oscilloscope: Clear"),
+                Command::Auto => ("ds1054z.auto", "Oscilloscope: Auto"),
+            };
+            let remotes = remotes.clone();
+            bobs::Hotkey::register_source(&source, name, description, move |pressed| {
+                if pressed {
+                    for remote in remotes.lock().unwrap().iter() {
+                        remote.command(command);
                     }
-                    settings = s;
-                    scope = None;
                 }
-                Err(mpsc::TryRecvError::Disconnected) => return,
-                Err(mpsc::TryRecvError::Empty) => (),
-            }
-
-            // don't busy-loop
-            let now = Instant::now();
-            if now < loop_end {
-                std::thread::sleep(loop_end - now);
-            }
+            })
+        })
+        .collect()
+}
+
+pub fn show(props: &mut bobs::Properties, names: &[&str], visible: bool) {
+    for name in names {
+        if let Some(mut p) = props.get(name) {
+            p.set_visible(visible);
         }
     }
 }
 
-impl Drop for ScopeSource {
-    fn drop(&mut self) {
-        if let Some(thread) = self.thread.take() {
-            // send end-thread message
-            self.channel
-                .send(Message::End)
-                .expect("could not end thread");
-            thread.join().expect("could not join thread");
+fn list_devices(device: &mut bobs::Property, serial: &str) {
+    device.list_clear();
+    device.list_add_string("Use address below", "");
+    let found = discovery::found();
+    // keep the saved scope selectable, even if it hasn't turned up yet
+    if !serial.is_empty() && !found.iter().any(|i| i.serial == serial) {
+        device.list_add_string(&format!("{} (not found)", serial), serial);
+    }
+    for instrument in found {
+        device.list_add_string(&instrument.to_string(), &instrument.serial);
+    }
+}
+
+// version 1 replaced the "blank" checkbox with on_disconnect
+pub const SETTINGS_VERSION: i64 = 1;
+
+// upgrade settings from older versions of the plugin, one step at a time
+pub fn migrate_settings(settings: &mut bobs::Data, from: i64) {
+    if from < 1 {
+        // blank was on by default, so an unset one still means blank. a
+        // source that saved nothing at all can't be told apart from a new
+        // one, so it never gets here and shows the overlay like new ones do.
+        let blank = !settings.has_user_value("blank") || settings.get_bool("blank");
+        settings.set_string("on_disconnect", if blank { "blank" } else { "hold" });
+        settings.erase("blank");
+    }
+}
+
+pub fn set_defaults(settings: &mut bobs::Data) {
+    Target::defaults(settings);
+    Common::defaults(settings);
+    Retry::defaults(settings);
+}
+
+pub fn read_settings(settings: &bobs::Data) -> Settings {
+    let target = Target::from_data(settings);
+    let common = Common::from_data(settings);
+    let retry = Retry::from_data(settings);
+    let millis = |ms: i64| Duration::from_millis(ms.max(0) as u64);
+    Settings {
+        address: target.address,
+        serial: target.device,
+        on_disconnect: common.disconnect(),
+        backoff: Backoff {
+            initial: millis(retry.retry_initial),
+            max: millis(retry.retry_max),
+            jitter: retry.retry_jitter as f64 / 100.0,
+            max_attempts: retry.retry_limit.max(0) as u32,
+            ..Default::default()
+        },
+        connect_timeout: millis(common.connect_timeout),
+        pacing: Pacing {
+            fps: common.fps.max(1) as f64,
+            adaptive: common.adaptive,
+        },
+        disconnect_paused: common.disconnect_paused,
+        trigger: common.trigger(),
+    }
+}
+
+// where OBS says a source is shown, and where it needs to be to capture
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Visibility {
+    // on the program output
+    active: bool,
+    // anywhere, including preview and projectors
+    showing: bool,
+    live_only: bool,
+}
+
+impl Visibility {
+    pub fn configure(&mut self, settings: &bobs::Data) {
+        self.live_only = Common::from_data(settings).run_when == "live";
+    }
+
+    pub fn paused(&self) -> bool {
+        if self.live_only {
+            !self.active
+        } else {
+            !self.showing
         }
     }
+
+    // for the activate and deactivate callbacks, returning paused()
+    pub fn set_active(&mut self, active: bool) -> bool {
+        self.active = active;
+        self.paused()
+    }
+
+    // for the show and hide callbacks, returning paused()
+    pub fn set_showing(&mut self, showing: bool) -> bool {
+        self.showing = showing;
+        self.paused()
+    }
+}
+
+impl Common {
+    fn disconnect(&self) -> Disconnect {
+        let secs = Duration::from_secs(self.disconnect_seconds.max(0) as u64);
+        match self.on_disconnect.as_str() {
+            "hold" => Disconnect::Hold,
+            "hold_then_blank" => Disconnect::HoldThenBlank(secs),
+            "fade" => Disconnect::FadeToBlack(secs),
+            "blank" => Disconnect::Blank,
+            _ => Disconnect::Overlay,
+        }
+    }
+
+    fn trigger(&self) -> Trigger {
+        match self.trigger.as_str() {
+            "single" => Trigger::Single { rearm: self.rearm },
+            _ => Trigger::Always,
+        }
+    }
+}
+
+// sends frames straight to an async-video OBS source
+#[derive(Debug)]
+pub struct ObsSink {
+    source: ThreadSafePtr<obs_sys::obs_source_t>,
+}
+
+impl ObsSink {
+    pub fn new(source: *mut obs_sys::obs_source_t) -> Self {
+        ObsSink {
+            source: ThreadSafePtr(source),
+        }
+    }
+}
+
+impl FrameSink for ObsSink {
+    fn frame(&mut self, frame: &Frame) {
+        let mut raw = obs_sys::obs_source_frame {
+            format: obs_sys::video_format_VIDEO_FORMAT_RGBA,
+            width: frame.width,
+            height: frame.height,
+            ..Default::default()
+        };
+        raw.linesize[0] = frame.linesize();
+        raw.data[0] = frame.data.as_ptr() as *mut u8;
+
+        // present texture
+        unsafe {
+            obs_sys::obs_source_output_video(self.source.0, &raw);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn migrated(json: &str, from: i64) -> Box<bobs::Data> {
+        let mut settings = bobs::Data::create_from_json(json).unwrap();
+        migrate_settings(&mut settings, from);
+        settings
+    }
+
+    #[test]
+    fn custom_crops_are_clipped_to_the_frame() {
+        let custom = |x, y, width, height| Crop::Custom {
+            x,
+            y,
+            width,
+            height,
+        };
+        assert_eq!(custom(10, 20, 30, 40).rect(800, 480), (10, 20, 30, 40));
+        assert_eq!(
+            custom(700, 400, 300, 300).rect(800, 480),
+            (700, 400, 100, 80)
+        );
+        // nothing left, so show the whole screen rather than nothing
+        assert_eq!(custom(800, 0, 100, 100).rect(800, 480), (0, 0, 800, 480));
+        assert_eq!(custom(0, 0, 0, 100).rect(800, 480), (0, 0, 800, 480));
+        assert_eq!(Crop::Graticule.rect(0, 0), (0, 0, 0, 0));
+    }
+
+    #[test]
+    fn blank_becomes_blank() {
+        let settings = migrated(r#"{"address": "scope:5555", "blank": true}"#, 0);
+        assert_eq!(settings.get_string("on_disconnect"), "blank");
+        assert!(!settings.has_user_value("blank"));
+    }
+
+    #[test]
+    fn not_blank_becomes_hold() {
+        let settings = migrated(r#"{"address": "scope:5555", "blank": false}"#, 0);
+        assert_eq!(settings.get_string("on_disconnect"), "hold");
+        assert!(!settings.has_user_value("blank"));
+    }
+
+    #[test]
+    fn unset_blank_means_blank() {
+        let settings = migrated(r#"{"address": "scope:5555"}"#, 0);
+        assert_eq!(settings.get_string("on_disconnect"), "blank");
+    }
+
+    #[test]
+    fn current_settings_are_left_alone() {
+        let json = r#"{"address": "scope:5555", "on_disconnect": "fade", "settings_version": 1}"#;
+        let mut settings = migrated(json, SETTINGS_VERSION);
+        let mut expected = bobs::Data::create_from_json(json).unwrap();
+        assert_eq!(settings.get_json(), expected.get_json());
+    }
 }
@@ -0,0 +1,340 @@
+use crate::measure::{prefix_exponent, with_prefix, Item, Measurement};
+
+// a measurement readout with placeholders, like "{CH1.VPP:.3} / {CH2.FREQ:k}"
+//
+// a placeholder is {CHn.ITEM} or {CHn.ITEM:SPEC}, where ITEM is one of the
+// names in Item::from_name. SPEC is ".N" for N decimals, followed by an SI
+// prefix to always use (p, n, u, m, k, M, G, or _ for none), and either
+// part can be left out. duty is a percentage, so it only takes the ".N".
+// {{ and }} are literal braces.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Field(Field),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Field {
+    pub channel: usize,
+    pub item: Item,
+    pub spec: Spec,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Spec {
+    // None to use the readout's significant digits
+    pub decimals: Option<usize>,
+    // the exponent of a fixed SI prefix, or None to pick one per value
+    pub exponent: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    Unclosed(String),
+    Unopened,
+    Channel(String),
+    Item(String),
+    Spec(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::Unclosed(p) => write!(f, "missing }} after {{{}", p),
+            Error::Unopened => write!(f, "}} without {{, write }}}} for a brace"),
+            Error::Channel(p) => write!(f, "{{{}}} should start with CH1 to CH4", p),
+            Error::Item(p) => write!(f, "{{{}}} has an unknown measurement", p),
+            Error::Spec(p) => write!(f, "{{{}}} has a bad format, like :.3 or :.1k", p),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Template {
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let mut parts = vec![];
+        let mut text = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '}' => return Err(Error::Unopened),
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            // a newline can't be inside one, so stop there
+                            Some('\n') | None => return Err(Error::Unclosed(placeholder)),
+                            Some(c) => placeholder.push(c),
+                        }
+                    }
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Field(Field::parse(&placeholder)?));
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Template { parts })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    // every channel and item the template shows, each once, in order
+    pub fn wanted(&self) -> Vec<(usize, Item)> {
+        let mut wanted = vec![];
+        for part in &self.parts {
+            if let Part::Field(f) = part {
+                if !wanted.contains(&(f.channel, f.item)) {
+                    wanted.push((f.channel, f.item));
+                }
+            }
+        }
+        wanted
+    }
+
+    // fields without a precision get `digits` significant digits
+    pub fn render(&self, measurements: &[Measurement], digits: usize) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(t) => out += t,
+                Part::Field(f) => {
+                    let value = measurements
+                        .iter()
+                        .find(|m| m.channel == f.channel && m.item == f.item)
+                        .and_then(|m| m.value);
+                    out += &f.format(value, digits);
+                }
+            }
+        }
+        out
+    }
+}
+
+impl Field {
+    // the inside of a placeholder, like "CH1.VPP:.3"
+    fn parse(placeholder: &str) -> Result<Self, Error> {
+        let (name, spec) = match placeholder.find(':') {
+            Some(i) => (&placeholder[..i], Some(&placeholder[i + 1..])),
+            None => (placeholder, None),
+        };
+        let name = name.trim().to_uppercase();
+        let (channel, item) = match name.find('.') {
+            Some(i) => (&name[..i], &name[i + 1..]),
+            None => (name.as_str(), ""),
+        };
+
+        let channel = channel
+            .strip_prefix("CH")
+            .and_then(|n| n.parse::<usize>().ok())
+            .filter(|n| (1..=4).contains(n))
+            .ok_or_else(|| Error::Channel(placeholder.to_owned()))?;
+        let item = Item::from_name(item).ok_or_else(|| Error::Item(placeholder.to_owned()))?;
+        let spec = match spec {
+            Some(s) => Spec::parse(s.trim())
+                .filter(|s| item != Item::Duty || s.exponent.is_none())
+                .ok_or_else(|| Error::Spec(placeholder.to_owned()))?,
+            None => Spec::default(),
+        };
+        Ok(Field {
+            channel,
+            item,
+            spec,
+        })
+    }
+
+    fn format(&self, value: Option<f64>, digits: usize) -> String {
+        let spec = self.spec;
+        if spec == Spec::default() {
+            return self.item.format(value, digits);
+        }
+        let value = match value {
+            Some(v) if v.is_finite() => v,
+            _ => return self.item.format(None, digits),
+        };
+        if self.item == Item::Duty {
+            // already a percentage, so there's no prefix to pick
+            let decimals = spec.decimals.unwrap_or_else(|| digits.saturating_sub(2));
+            return format!("{:.*} %", decimals, 100.0 * value);
+        }
+
+        let exponent = match spec.exponent {
+            Some(e) => e,
+            None => {
+                let e = magnitude(value).map(prefix_exponent).unwrap_or(0);
+                // 999.96 with one decimal rounds to 1000.0, which wants the next prefix
+                let decimals = spec.decimals.unwrap_or(0);
+                let scaled = value / 10f64.powi(e);
+                let step = 10f64.powi(-(decimals as i32));
+                if ((scaled / step).round() * step).abs() >= 1000.0 && e < 9 {
+                    e + 3
+                } else {
+                    e
+                }
+            }
+        };
+        let decimals = spec.decimals.unwrap_or_else(|| {
+            let digits = digits.max(1) as i32;
+            let shown = magnitude(value).map(|m| m - exponent).unwrap_or(0);
+            (digits - 1 - shown).max(0) as usize
+        });
+        with_prefix(value, exponent, decimals, self.item.unit())
+    }
+}
+
+impl Spec {
+    fn parse(s: &str) -> Option<Self> {
+        let mut spec = Spec::default();
+        let mut rest = s;
+        if let Some(r) = rest.strip_prefix('.') {
+            let end = r.find(|c: char| !c.is_ascii_digit()).unwrap_or(r.len());
+            spec.decimals = Some(r[..end].parse::<usize>().ok().filter(|d| *d <= 9)?);
+            rest = &r[end..];
+        }
+        let mut prefix = rest.chars();
+        spec.exponent = match prefix.next() {
+            None => None,
+            Some(c) => Some(match c {
+                'p' => -12,
+                'n' => -9,
+                'u' | 'µ' | 'μ' => -6,
+                'm' => -3,
+                '_' => 0,
+                'k' => 3,
+                'M' => 6,
+                'G' => 9,
+                _ => return None,
+            }),
+        };
+        if prefix.next().is_some() {
+            return None;
+        }
+        Some(spec)
+    }
+}
+
+// the power of ten of a value's leading digit, or None for zero
+fn magnitude(value: f64) -> Option<i32> {
+    if value == 0.0 {
+        None
+    } else {
+        Some(value.abs().log10().floor() as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(s: &str) -> Option<(Option<usize>, Option<i32>)> {
+        Spec::parse(s).map(|s| (s.decimals, s.exponent))
+    }
+
+    fn render(template: &str, item: Item, value: f64) -> String {
+        let measurement = Measurement {
+            channel: 1,
+            item,
+            value: Some(value),
+        };
+        Template::parse(template).unwrap().render(&[measurement], 3)
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        let template = Template::parse("{{CH1.VPP}} }}{{").unwrap();
+        assert!(template.wanted().is_empty());
+        assert_eq!(template.render(&[], 3), "{CH1.VPP} }{");
+    }
+
+    #[test]
+    fn bad_templates_say_why() {
+        let error = |s| Template::parse(s).unwrap_err();
+        assert_eq!(error("Vpp {CH1.VPP"), Error::Unclosed("CH1.VPP".to_owned()));
+        assert_eq!(error("{CH1.VPP\n}"), Error::Unclosed("CH1.VPP".to_owned()));
+        assert_eq!(error("Vpp }"), Error::Unopened);
+        assert_eq!(error("{CH5.VPP}"), Error::Channel("CH5.VPP".to_owned()));
+        assert_eq!(error("{VPP}"), Error::Channel("VPP".to_owned()));
+        assert_eq!(error("{CH1.WATTS}"), Error::Item("CH1.WATTS".to_owned()));
+        assert_eq!(error("{CH1}"), Error::Item("CH1".to_owned()));
+        assert_eq!(error("{CH1.VPP:x}"), Error::Spec("CH1.VPP:x".to_owned()));
+    }
+
+    #[test]
+    fn specs_take_decimals_then_a_prefix() {
+        assert_eq!(spec(""), Some((None, None)));
+        assert_eq!(spec(".3"), Some((Some(3), None)));
+        assert_eq!(spec("k"), Some((None, Some(3))));
+        assert_eq!(spec(".1M"), Some((Some(1), Some(6))));
+        assert_eq!(spec("_"), Some((None, Some(0))));
+        assert_eq!(spec("µ"), Some((None, Some(-6))));
+        assert_eq!(spec("u"), Some((None, Some(-6))));
+    }
+
+    #[test]
+    fn bad_specs_are_rejected() {
+        assert_eq!(spec(".10"), None);
+        assert_eq!(spec("kk"), None);
+        assert_eq!(spec("."), None);
+        assert_eq!(spec("k.1"), None);
+        assert_eq!(spec("K"), None);
+    }
+
+    #[test]
+    fn duty_takes_no_prefix() {
+        let error = Template::parse("{CH1.PDUT:k}").unwrap_err();
+        assert_eq!(error, Error::Spec("CH1.PDUT:k".to_owned()));
+        assert_eq!(render("{CH1.PDUT:.1}", Item::Duty, 0.25), "25.0 %");
+    }
+
+    #[test]
+    fn wanted_lists_each_measurement_once() {
+        let template = Template::parse("{CH1.VPP} {CH2.FREQ} {ch1.vpp:.1} {CH2.Freq:k}").unwrap();
+        assert_eq!(
+            template.wanted(),
+            vec![(1, Item::Vpp), (2, Item::Frequency)]
+        );
+    }
+
+    #[test]
+    fn fields_are_formatted_by_their_spec() {
+        assert_eq!(render("{CH1.FREQ}", Item::Frequency, 1234.0), "1.23 kHz");
+        assert_eq!(
+            render("{CH1.FREQ:.3}", Item::Frequency, 1234.0),
+            "1.234 kHz"
+        );
+        assert_eq!(render("{CH1.FREQ:_}", Item::Frequency, 1234.0), "1234 Hz");
+        assert_eq!(render("{CH1.VPP:.1m}", Item::Vpp, 0.5), "500.0 mV");
+    }
+
+    #[test]
+    fn rounding_up_moves_to_the_next_prefix() {
+        assert_eq!(render("{CH1.FREQ:.1}", Item::Frequency, 999.96), "1.0 kHz");
+        assert_eq!(render("{CH1.FREQ:.1}", Item::Frequency, 999.94), "999.9 Hz");
+        // unless the prefix is fixed
+        assert_eq!(
+            render("{CH1.FREQ:.1_}", Item::Frequency, 999.96),
+            "1000.0 Hz"
+        );
+    }
+}
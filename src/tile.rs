@@ -0,0 +1,129 @@
+use crate::capture::{Event, Frame, FrameSink};
+use crate::render::Color;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Layout {
+    Horizontal,
+    Vertical,
+    Grid,
+}
+
+impl Layout {
+    // (columns, rows) needed to fit n tiles
+    pub fn grid(&self, n: usize) -> (usize, usize) {
+        let n = n.max(1);
+        match self {
+            Layout::Horizontal => (n, 1),
+            Layout::Vertical => (1, n),
+            Layout::Grid => {
+                let cols = (n as f64).sqrt().ceil() as usize;
+                // div_ceil is newer than the compilers this builds with
+                #[allow(clippy::manual_div_ceil)]
+                let rows = (n + cols - 1) / cols;
+                (cols, rows)
+            }
+        }
+    }
+}
+
+// composes the latest frame from each of several engines into one
+#[derive(Debug)]
+pub struct Compositor<S> {
+    layout: Layout,
+    tiles: Vec<Option<Frame>>,
+    out: Frame,
+    sink: S,
+}
+
+impl<S> Compositor<S>
+where
+    S: FrameSink,
+{
+    pub fn new(sink: S, layout: Layout, n: usize) -> Self {
+        Compositor {
+            layout,
+            tiles: vec![None; n],
+            out: Frame::new(0, 0),
+            sink,
+        }
+    }
+
+    pub fn set_layout(&mut self, layout: Layout, n: usize) {
+        self.layout = layout;
+        self.tiles.resize(n, None);
+    }
+
+    pub fn tile(&mut self, index: usize, frame: &Frame) {
+        match self.tiles.get_mut(index) {
+            Some(Some(tile)) => tile.clone_from(frame),
+            Some(tile) => *tile = Some(frame.clone()),
+            // a tile that was just removed
+            None => return,
+        }
+        self.compose();
+        self.sink.frame(&self.out);
+    }
+
+    fn compose(&mut self) {
+        // every cell is as big as the biggest tile
+        let cell_w = self
+            .tiles
+            .iter()
+            .flatten()
+            .map(|f| f.width)
+            .max()
+            .unwrap_or(0);
+        let cell_h = self
+            .tiles
+            .iter()
+            .flatten()
+            .map(|f| f.height)
+            .max()
+            .unwrap_or(0);
+        let (cols, rows) = self.layout.grid(self.tiles.len());
+        self.out.resize(cols as u32 * cell_w, rows as u32 * cell_h);
+        self.out.fill(Color::BLACK);
+
+        let stride = self.out.linesize() as usize;
+        for (i, tile) in self.tiles.iter().enumerate() {
+            let tile = match tile {
+                Some(t) if t.width > 0 => t,
+                _ => continue,
+            };
+            let x0 = (i % cols) * cell_w as usize;
+            let y0 = (i / cols) * cell_h as usize;
+            let row_bytes = tile.linesize() as usize;
+            for (row, src) in tile.data.chunks_exact(row_bytes).enumerate() {
+                let dst = (y0 + row) * stride + 4 * x0;
+                self.out.data[dst..dst + row_bytes].copy_from_slice(src);
+            }
+        }
+    }
+}
+
+// feeds one engine's frames into its cell of a shared compositor
+#[derive(Debug)]
+pub struct TileSink<S> {
+    index: usize,
+    compositor: Arc<Mutex<Compositor<S>>>,
+}
+
+impl<S> TileSink<S> {
+    pub fn new(index: usize, compositor: Arc<Mutex<Compositor<S>>>) -> Self {
+        TileSink { index, compositor }
+    }
+}
+
+impl<S> FrameSink for TileSink<S>
+where
+    S: FrameSink,
+{
+    fn frame(&mut self, frame: &Frame) {
+        self.compositor.lock().unwrap().tile(self.index, frame);
+    }
+
+    fn event(&mut self, event: &Event) {
+        self.compositor.lock().unwrap().sink.event(event);
+    }
+}
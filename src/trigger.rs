@@ -0,0 +1,184 @@
+use crate::capture::Command;
+use crate::scpi;
+
+// what :TRIGger:STATus? answers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Status {
+    // TD, just triggered
+    Triggered,
+    // WAIT, armed and waiting for a trigger
+    Waiting,
+    // RUN, filling the pre-trigger part of memory
+    Running,
+    // AUTO, sweeping without a trigger
+    Auto,
+    // STOP, done acquiring
+    Stopped,
+}
+
+impl Status {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_uppercase().as_str() {
+            "TD" => Some(Status::Triggered),
+            "WAIT" => Some(Status::Waiting),
+            "RUN" => Some(Status::Running),
+            "AUTO" => Some(Status::Auto),
+            "STOP" => Some(Status::Stopped),
+            _ => None,
+        }
+    }
+
+    // on the way to a triggered acquisition
+    pub fn armed(self) -> bool {
+        matches!(self, Status::Triggered | Status::Waiting | Status::Running)
+    }
+}
+
+pub async fn status(scope: &mut ds1054z::Scope) -> Result<Status, scpi::Error> {
+    let r = scpi::query(scope, ":TRIG:STAT?").await?;
+    Status::parse(&r).ok_or(scpi::Error::Parse(r))
+}
+
+// what :TRIGger:SWEep? answers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Sweep {
+    Auto,
+    Normal,
+    Single,
+}
+
+impl Sweep {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_uppercase().as_str() {
+            "AUTO" => Some(Sweep::Auto),
+            "NORM" | "NORMAL" => Some(Sweep::Normal),
+            "SING" | "SINGLE" => Some(Sweep::Single),
+            _ => None,
+        }
+    }
+}
+
+pub async fn sweep(scope: &mut ds1054z::Scope) -> Result<Sweep, scpi::Error> {
+    let r = scpi::query(scope, ":TRIG:SWE?").await?;
+    Sweep::parse(&r).ok_or(scpi::Error::Parse(r))
+}
+
+// spots single-shot acquisitions finishing, by polling the trigger status.
+// the scope goes from WAIT (or RUN, or TD) to STOP once per acquisition,
+// but only a STOP in the single sweep means one finished.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Watch {
+    // seen armed since the last acquisition finished
+    armed: bool,
+    // don't re-arm until SINGLE is pressed again
+    held: bool,
+}
+
+impl Watch {
+    // true once for each acquisition that has finished since the last poll.
+    // with `rearm`, a stopped scope is put back into SINGLE afterwards.
+    pub async fn poll(
+        &mut self,
+        scope: &mut ds1054z::Scope,
+        rearm: bool,
+    ) -> Result<bool, scpi::Error> {
+        let status = status(scope).await?;
+        // stopped from the front panel, or by another client, so neither
+        // an acquisition nor ours to re-arm
+        if status == Status::Stopped && sweep(scope).await? != Sweep::Single {
+            self.armed = false;
+            return Ok(false);
+        }
+        if self.finished(status) {
+            return Ok(true);
+        }
+        // after a grab, or if the scope was already stopped when we started
+        if rearm && !self.held && status == Status::Stopped {
+            scpi::send(scope, Command::Single.scpi()).await?;
+            self.armed = true;
+        }
+        Ok(false)
+    }
+
+    fn finished(&mut self, status: Status) -> bool {
+        match status {
+            s if s.armed() => {
+                self.armed = true;
+                false
+            }
+            Status::Stopped => std::mem::replace(&mut self.armed, false),
+            _ => {
+                self.armed = false;
+                false
+            }
+        }
+    }
+
+    // a button was pressed on the scope through us
+    pub fn pressed(&mut self, command: Command) {
+        match command {
+            // armed even if the trigger comes before the next poll
+            Command::Single => {
+                self.armed = true;
+                self.held = false;
+            }
+            // the scope stops, but nothing was captured
+            Command::Stop | Command::Run | Command::Auto => {
+                self.armed = false;
+                self.held = true;
+            }
+            Command::Clear => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockScope;
+
+    fn singles(mock: &MockScope) -> usize {
+        mock.requests()
+            .iter()
+            .filter(|r| r.to_uppercase().starts_with(":SING"))
+            .count()
+    }
+
+    #[test]
+    fn single_acquisitions_count_once_and_rearm() {
+        let mock = MockScope::start().unwrap();
+        mock.set_sweep("SING");
+        mock.set_trigger("WAIT");
+        smol::block_on(async {
+            let mut scope = ds1054z::Scope::connect(&mock.address()).await.unwrap();
+            let mut watch = Watch::default();
+            assert!(!watch.poll(&mut scope, true).await.unwrap());
+
+            mock.set_trigger("STOP");
+            assert!(watch.poll(&mut scope, true).await.unwrap());
+            assert_eq!(singles(&mock), 0);
+            // still stopped, so armed again instead of counted twice
+            assert!(!watch.poll(&mut scope, true).await.unwrap());
+            assert!(!watch.poll(&mut scope, true).await.unwrap());
+            // :SING has no reply, so only count it once a later query is answered
+            assert_eq!(singles(&mock), 1);
+        });
+    }
+
+    #[test]
+    fn stopping_another_sweep_is_not_an_acquisition() {
+        let mock = MockScope::start().unwrap();
+        mock.set_sweep("AUTO");
+        mock.set_trigger("RUN");
+        smol::block_on(async {
+            let mut scope = ds1054z::Scope::connect(&mock.address()).await.unwrap();
+            let mut watch = Watch::default();
+            assert!(!watch.poll(&mut scope, true).await.unwrap());
+
+            mock.set_trigger("STOP");
+            assert!(!watch.poll(&mut scope, true).await.unwrap());
+            assert!(!watch.poll(&mut scope, true).await.unwrap());
+            assert_eq!(singles(&mock), 0);
+        });
+    }
+}
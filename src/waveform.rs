@@ -0,0 +1,247 @@
+use crate::capture::{Frame, Grabber, Worker};
+use crate::render::Color;
+use crate::scpi;
+use crate::source::{
+    add_properties, migrate_settings, read_settings, register_hotkeys, set_defaults, status_line,
+    ObsSink, Remotes, Visibility, ADDRESS_HELP, SETTINGS_VERSION,
+};
+use bobs::Settings as _;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+pub const CHANNELS: usize = 4;
+
+// the scope screen is 12 divisions wide and 8 tall
+const HDIVS: u32 = 12;
+const VDIVS: u32 = 8;
+
+// the scope's own trace colors
+const DEFAULT_COLORS: [Color; CHANNELS] = [
+    Color::rgb(0xff, 0xff, 0x00),
+    Color::rgb(0x00, 0xff, 0xff),
+    Color::rgb(0xff, 0x00, 0xff),
+    Color::rgb(0x00, 0x80, 0xff),
+];
+
+bobs::settings! {
+    #[derive(Debug, Clone)]
+    pub struct Look {
+        pub graticule: bool = true, "Draw graticule", Bool;
+        pub line_width: i64 = 2, "Line width", IntSlider { min: 1, max: 10, step: 1 };
+        pub color1: i64 = DEFAULT_COLORS[0].to_obs(), "Channel 1 color", Color;
+        pub color2: i64 = DEFAULT_COLORS[1].to_obs(), "Channel 2 color", Color;
+        pub color3: i64 = DEFAULT_COLORS[2].to_obs(), "Channel 3 color", Color;
+        pub color4: i64 = DEFAULT_COLORS[3].to_obs(), "Channel 4 color", Color;
+    }
+}
+
+#[derive(Debug)]
+pub struct WaveformSource {
+    // unregistered when dropped
+    _hotkeys: Vec<bobs::Hotkey>,
+    worker: Worker<Waveform>,
+    serial: String,
+    visibility: Visibility,
+}
+
+// renders traces from :WAV:DATA? instead of grabbing the screen
+#[derive(Debug, Clone)]
+pub struct Waveform {
+    pub width: u32,
+    pub height: u32,
+    pub colors: [Color; CHANNELS],
+    pub line_width: u32,
+    pub graticule: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Trace {
+    pub channel: usize,
+    // volts per division
+    pub scale: f64,
+    // volts
+    pub offset: f64,
+    pub samples: Vec<f64>,
+}
+
+impl bobs::SourceImpl for WaveformSource {
+    const ID: &'static str = "ds1054z_waveform";
+    const NAME: &'static str = "Rigol DS1054Z Waveform";
+    const ICON_TYPE: bobs::IconType = bobs::IconType::Custom;
+    const VERSION: i64 = SETTINGS_VERSION;
+
+    fn output_flags() -> bobs::SourceFlags {
+        bobs::SourceFlags::ASYNC_VIDEO
+    }
+
+    fn callbacks() -> bobs::Callbacks {
+        bobs::Callbacks::ACTIVATE | bobs::Callbacks::SHOW
+    }
+
+    fn create(settings: &bobs::Data, source: *mut obs_sys::obs_source_t) -> Self {
+        let worker = Worker::spawn(ObsSink::new(source), Waveform::default());
+        let remotes: Remotes<Waveform> = Arc::new(Mutex::new(vec![worker.remote()]));
+        let mut src = WaveformSource {
+            _hotkeys: register_hotkeys(source, &remotes),
+            worker,
+            serial: String::new(),
+            visibility: Visibility::default(),
+        };
+        src.update(settings);
+        src
+    }
+
+    fn get_properties(&mut self) -> Box<bobs::Properties> {
+        let mut props = bobs::Properties::create();
+        add_properties(
+            &mut props,
+            &self.serial,
+            ADDRESS_HELP,
+            &status_line(&self.worker),
+        );
+        Look::properties(&mut props);
+        props
+    }
+
+    fn get_defaults(settings: &mut bobs::Data) {
+        set_defaults(settings);
+        Look::defaults(settings);
+    }
+
+    fn migrate(settings: &mut bobs::Data, from: i64) {
+        migrate_settings(settings, from);
+    }
+
+    fn update(&mut self, settings: &bobs::Data) {
+        let look = Look::from_data(settings);
+        let waveform = Waveform {
+            graticule: look.graticule,
+            line_width: look.line_width as u32,
+            colors: [look.color1, look.color2, look.color3, look.color4].map(Color::from_obs),
+            ..Default::default()
+        };
+        self.visibility.configure(settings);
+        let settings = read_settings(settings);
+        self.serial = settings.serial.clone();
+        self.worker.update(settings, waveform);
+        self.worker.set_paused(self.visibility.paused());
+    }
+
+    fn activate(&mut self) {
+        self.worker.set_paused(self.visibility.set_active(true));
+    }
+
+    fn deactivate(&mut self) {
+        self.worker.set_paused(self.visibility.set_active(false));
+    }
+
+    fn show(&mut self) {
+        self.worker.set_paused(self.visibility.set_showing(true));
+    }
+
+    fn hide(&mut self) {
+        self.worker.set_paused(self.visibility.set_showing(false));
+    }
+}
+
+impl Default for Waveform {
+    fn default() -> Self {
+        // render at the canvas resolution, so traces stay crisp
+        let (width, height) = bobs::get_video_info()
+            .map(|ovi| (ovi.base_width, ovi.base_height))
+            .unwrap_or((1920, 1080));
+        Waveform {
+            width,
+            height,
+            colors: DEFAULT_COLORS,
+            line_width: 2,
+            graticule: true,
+        }
+    }
+}
+
+impl Waveform {
+    pub async fn fetch(scope: &mut ds1054z::Scope) -> Result<Vec<Trace>, scpi::Error> {
+        // NORMal mode returns exactly the points on screen
+        scpi::send(scope, ":WAV:MODE NORM").await?;
+        scpi::send(scope, ":WAV:FORM ASC").await?;
+
+        let mut traces = vec![];
+        for channel in 1..=CHANNELS {
+            if !scpi::query_bool(scope, &format!(":CHAN{}:DISP?", channel)).await? {
+                continue;
+            }
+            let scale = scpi::query_f64(scope, &format!(":CHAN{}:SCAL?", channel)).await?;
+            let offset = scpi::query_f64(scope, &format!(":CHAN{}:OFFS?", channel)).await?;
+            scpi::send(scope, &format!(":WAV:SOUR CHAN{}", channel)).await?;
+            let data = scpi::query(scope, ":WAV:DATA?").await?;
+            traces.push(Trace {
+                channel,
+                scale,
+                offset,
+                samples: scpi::parse_ascii_waveform(&data)?,
+            });
+        }
+        Ok(traces)
+    }
+
+    pub fn render(&self, traces: &[Trace], frame: &mut Frame) {
+        frame.resize(self.width, self.height);
+        frame.fill(Color::BLACK);
+        if self.graticule {
+            self.draw_graticule(frame);
+        }
+
+        let w = self.width as f64;
+        let h = self.height as f64;
+        for trace in traces {
+            if trace.samples.len() < 2 || trace.scale == 0.0 {
+                continue;
+            }
+            let color = self.colors[trace.channel - 1];
+            let dx = (w - 1.0) / (trace.samples.len() - 1) as f64;
+            let to_y = |v: f64| h / 2.0 - (v + trace.offset) / trace.scale * h / VDIVS as f64;
+
+            let mut prev = (0.0, to_y(trace.samples[0]));
+            for (i, v) in trace.samples.iter().enumerate().skip(1) {
+                let next = (i as f64 * dx, to_y(*v));
+                frame.line(prev.0, prev.1, next.0, next.1, self.line_width, color);
+                prev = next;
+            }
+        }
+    }
+
+    fn draw_graticule(&self, frame: &mut Frame) {
+        let grid = Color::rgba(0xff, 0xff, 0xff, 0x40);
+        let axis = Color::rgba(0xff, 0xff, 0xff, 0x80);
+        for i in 0..=HDIVS {
+            let x = (i * self.width.saturating_sub(1) / HDIVS) as i32;
+            frame.vline(x, if i == HDIVS / 2 { axis } else { grid }, i != HDIVS / 2);
+        }
+        for i in 0..=VDIVS {
+            let y = (i * self.height.saturating_sub(1) / VDIVS) as i32;
+            frame.hline(y, if i == VDIVS / 2 { axis } else { grid }, i != VDIVS / 2);
+        }
+    }
+
+    async fn grab_waveform(
+        &mut self,
+        scope: &mut ds1054z::Scope,
+        frame: &mut Frame,
+    ) -> Result<(), scpi::Error> {
+        let traces = Self::fetch(scope).await?;
+        self.render(&traces, frame);
+        Ok(())
+    }
+}
+
+impl Grabber for Waveform {
+    fn grab<'a>(
+        &'a mut self,
+        scope: &'a mut ds1054z::Scope,
+        frame: &'a mut Frame,
+    ) -> Pin<Box<dyn Future<Output = Result<(), scpi::Error>> + 'a>> {
+        Box::pin(self.grab_waveform(scope, frame))
+    }
+}